@@ -0,0 +1,50 @@
+//! Throughput of `prepare_images` depending on the chunk size.
+//!
+//! The benchmark expects the `Megatron` model and the test images to be in the current directory.
+use baklava::Runtime;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const IMAGES_COUNT: usize = 16;
+const CHUNK_SIZES: [usize; 5] = [1, 2, 4, 8, 16];
+
+fn prepare_images(c: &mut Criterion) {
+    let images = ["./face1_test.png", "./face2_test.png"]
+        .into_iter()
+        .cycle()
+        .take(IMAGES_COUNT)
+        .collect::<Vec<_>>();
+
+    let pool_size = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    // Every handler shares the runtime, hence the SDK is launched once for the whole benchmark
+    let runtime = Runtime::launch("./Megatron").expect("Unable to load the model");
+
+    let mut group = c.benchmark_group("prepare_images");
+    group.throughput(Throughput::Elements(IMAGES_COUNT as u64));
+    group.sample_size(10);
+
+    for chunk_size in CHUNK_SIZES {
+        let mut handler = runtime
+            .handler(Some(chunk_size), pool_size)
+            .expect("Unable to create the handler");
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            &images,
+            |b, images| {
+                b.iter(|| {
+                    handler
+                        .prepare_images(images)
+                        .expect("Unable to prepare the images");
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, prepare_images);
+criterion_main!(benches);
@@ -0,0 +1,149 @@
+/*
+ * C ABI of baklava. Every function returning a BaklavaStatus writes its result into the out parameters and
+ * returns BAKLAVA_STATUS_OK on success. On failure, baklava_last_error returns the message of the error.
+ */
+
+#ifndef BAKLAVA_H
+#define BAKLAVA_H
+
+/* Generated with cbindgen from bindings/c/src/lib.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Status returned by the functions of the C ABI
+typedef enum BaklavaStatus {
+  BAKLAVA_STATUS_OK = 0,
+  // A pointer is null, a string is not valid UTF-8 or a buffer does not have the expected length
+  BAKLAVA_STATUS_INVALID_ARGUMENT = 1,
+  // The model pack can not be loaded
+  BAKLAVA_STATUS_MODEL_LOAD = 2,
+  // A session can not be created
+  BAKLAVA_STATUS_SESSION = 3,
+  // The image can not be loaded or is not valid
+  BAKLAVA_STATUS_IMAGE = 4,
+  // No face can be tracked in the image
+  BAKLAVA_STATUS_FACE_TRACK = 5,
+  // The feature of the face can not be extracted
+  BAKLAVA_STATUS_FEATURE = 6,
+  // The embeddings can not be compared
+  BAKLAVA_STATUS_COMPARISON = 7,
+  // The embeddings have been extracted by another model pack
+  BAKLAVA_STATUS_MODEL = 8,
+  // A Rust panic has been caught
+  BAKLAVA_STATUS_PANIC = 9,
+  // Any other error
+  BAKLAVA_STATUS_ERROR = 10,
+} BaklavaStatus;
+
+// Methodology used to aggregate the cosines of several sources. It's passed as an `uint32_t` to
+// `baklava_compare`, any other value is rejected.
+typedef enum BaklavaMethodology {
+  BAKLAVA_METHODOLOGY_MEAN = 0,
+  BAKLAVA_METHODOLOGY_MEDIAN = 1,
+} BaklavaMethodology;
+
+// Handler running the InspireFace pipeline of baklava. A handler can be shared by several threads, at most one
+// call per session of its pool runs at the same time.
+typedef struct BaklavaHandler BaklavaHandler;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Return the message of the last error which happened on the calling thread, or NULL when no error happened. The
+// message is valid until the next failing call on the same thread.
+const char *baklava_last_error(void);
+
+// Create a handler loading the model pack at `model`, with `pool_size` sessions. The handler needs to be released
+// with `baklava_handler_free`.
+//
+// # Safety
+//
+// `model` needs to be a nul terminated string and `out` a valid pointer.
+enum BaklavaStatus baklava_handler_new(const char *model,
+                                       size_t pool_size,
+                                       struct BaklavaHandler **out);
+
+// Release a handler. Passing NULL does nothing.
+//
+// # Safety
+//
+// `handler` needs to have been created by `baklava_handler_new` and not be used afterward.
+void baklava_handler_free(struct BaklavaHandler *handler);
+
+// Return the number of values of the embeddings extracted by the handler, or 0 when the handler is NULL
+//
+// # Safety
+//
+// `handler` needs to be NULL or a handler created by `baklava_handler_new`.
+size_t baklava_feature_length(const struct BaklavaHandler *handler);
+
+// Return the cosine threshold recommended by the loaded model pack, or NaN when the handler is NULL
+//
+// # Safety
+//
+// `handler` needs to be NULL or a handler created by `baklava_handler_new`.
+float baklava_recommended_threshold(const struct BaklavaHandler *handler);
+
+// Extract the embedding of the face of the image at `path` into `out`, which holds `len` values
+//
+// # Safety
+//
+// `handler` needs to be a handler created by `baklava_handler_new`, `path` a nul terminated string and `out` a
+// buffer of `len` floats.
+enum BaklavaStatus baklava_extract(const struct BaklavaHandler *handler,
+                                   const char *path,
+                                   float *out,
+                                   size_t len);
+
+// Extract the embedding of the face of an image given as raw pixels (BGR for 3 channels, gray for 1 channel) into
+// `out`, which holds `len` values
+//
+// # Safety
+//
+// `handler` needs to be a handler created by `baklava_handler_new`, `pixels` a buffer of
+// `width * height * channels` bytes and `out` a buffer of `len` floats.
+enum BaklavaStatus baklava_extract_pixels(const struct BaklavaHandler *handler,
+                                          const uint8_t *pixels,
+                                          int32_t width,
+                                          int32_t height,
+                                          int32_t channels,
+                                          float *out,
+                                          size_t len);
+
+// Compare `sources_count` source embeddings, stored one after another in `sources`, against the `target`
+// embedding. Each embedding holds `len` values. The cosine and the percentage of similarity are written to `cosine`
+// and `percentage`. `methodology` is one of the `BaklavaMethodology` values.
+//
+// # Safety
+//
+// `handler` needs to be a handler created by `baklava_handler_new`, `sources` a buffer of `sources_count * len`
+// floats, `target` a buffer of `len` floats, `cosine` and `percentage` valid pointers.
+enum BaklavaStatus baklava_compare(const struct BaklavaHandler *handler,
+                                   const float *sources,
+                                   size_t sources_count,
+                                   const float *target,
+                                   size_t len,
+                                   uint32_t methodology,
+                                   float *cosine,
+                                   double *percentage);
+
+// Return whether the cosine is above the threshold. The threshold recommended by the model pack is used when
+// `threshold` is NULL. Return false when the handler is NULL.
+//
+// # Safety
+//
+// `handler` needs to be NULL or a handler created by `baklava_handler_new` and `threshold` NULL or a valid pointer.
+bool baklava_is_similar(const struct BaklavaHandler *handler,
+                        float cosine,
+                        const double *threshold);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BAKLAVA_H */
@@ -0,0 +1,467 @@
+//! C ABI of baklava for non-Rust consumers. The header `include/baklava.h` is generated with cbindgen and committed,
+//! a test checks that it matches the sources. It's regenerated from `bindings/c` with
+//! `cbindgen --config cbindgen.toml --output include/baklava.h`.
+//!
+//! Embeddings are exchanged as float buffers of `baklava_feature_length` values. Every function returning a
+//! `BaklavaStatus` writes its result into its out parameters and returns `BAKLAVA_STATUS_OK` on success. On failure,
+//! `baklava_last_error` returns the message of the error which happened last on the calling thread.
+use baklava::error::FFIError;
+use baklava::{FaceEmbedding, FaceImage, InsightFace, Methodology};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Status returned by the functions of the C ABI
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaklavaStatus {
+    Ok = 0,
+    /// A pointer is null, a string is not valid UTF-8 or a buffer does not have the expected length
+    InvalidArgument = 1,
+    /// The model pack can not be loaded
+    ModelLoad = 2,
+    /// A session can not be created
+    Session = 3,
+    /// The image can not be loaded or is not valid
+    Image = 4,
+    /// No face can be tracked in the image
+    FaceTrack = 5,
+    /// The feature of the face can not be extracted
+    Feature = 6,
+    /// The embeddings can not be compared
+    Comparison = 7,
+    /// The embeddings have been extracted by another model pack
+    Model = 8,
+    /// A Rust panic has been caught
+    Panic = 9,
+    /// Any other error
+    Error = 10,
+}
+
+/// Methodology used to aggregate the cosines of several sources. It's passed as an `uint32_t` to
+/// `baklava_compare`, any other value is rejected.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaklavaMethodology {
+    Mean = 0,
+    Median = 1,
+}
+
+/// Read the methodology passed by the caller, which is one of the values of `BaklavaMethodology`
+///
+/// # Arguments
+///
+/// * `methodology` - u32
+fn read_methodology(methodology: u32) -> Result<Methodology, Failure> {
+    match methodology {
+        m if m == BaklavaMethodology::Mean as u32 => Ok(Methodology::Mean),
+        m if m == BaklavaMethodology::Median as u32 => Ok(Methodology::Median),
+        _ => Err(Failure::invalid("the methodology is unknown")),
+    }
+}
+
+/// Handler running the InspireFace pipeline of baklava. A handler can be shared by several threads, at most one
+/// call per session of its pool runs at the same time.
+pub struct BaklavaHandler(InsightFace);
+
+/// Failure is an error along with the status returned to the caller
+struct Failure {
+    status: BaklavaStatus,
+    message: String,
+}
+
+impl Failure {
+    fn invalid(message: &str) -> Self {
+        Self {
+            status: BaklavaStatus::InvalidArgument,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<FFIError<'_>> for Failure {
+    fn from(err: FFIError<'_>) -> Self {
+        let status = match err {
+            FFIError::ModelLoad => BaklavaStatus::ModelLoad,
+            FFIError::Session => BaklavaStatus::Session,
+            FFIError::MissingImage | FFIError::Bitmap(_) | FFIError::Stream(_) => {
+                BaklavaStatus::Image
+            }
+            FFIError::FaceTrack(_) => BaklavaStatus::FaceTrack,
+            FFIError::Feature => BaklavaStatus::Feature,
+            FFIError::Comparison(_) | FFIError::SamplingSize => BaklavaStatus::Comparison,
+            FFIError::Model(_) => BaklavaStatus::Model,
+            _ => BaklavaStatus::Error,
+        };
+
+        Self {
+            status,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Run a call of the C ABI. The error is stored for `baklava_last_error` and panics are caught before they reach
+/// the caller.
+///
+/// # Arguments
+///
+/// * `call` - F
+fn run<F>(call: F) -> BaklavaStatus
+where
+    F: FnOnce() -> Result<(), Failure>,
+{
+    let failure = match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => return BaklavaStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure {
+            status: BaklavaStatus::Panic,
+            message: "a panic occurred within baklava".to_string(),
+        },
+    };
+
+    // Messages are formatted by baklava, hence they never hold a nul byte
+    let message = CString::new(failure.message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+
+    failure.status
+}
+
+/// Return the string behind a pointer
+///
+/// # Arguments
+///
+/// * `value` - *const c_char
+unsafe fn read_str<'a>(value: *const c_char) -> Result<&'a str, Failure> {
+    if value.is_null() {
+        return Err(Failure::invalid("a string is null"));
+    }
+
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| Failure::invalid("a string is not valid UTF-8"))
+}
+
+/// Return the handler behind a pointer
+///
+/// # Arguments
+///
+/// * `handler` - *const BaklavaHandler
+unsafe fn read_handler<'a>(handler: *const BaklavaHandler) -> Result<&'a InsightFace, Failure> {
+    handler
+        .as_ref()
+        .map(|handler| &handler.0)
+        .ok_or(Failure::invalid("the handler is null"))
+}
+
+/// Copy an embedding into the buffer of the caller
+///
+/// # Arguments
+///
+/// * `embedding` - FaceEmbedding
+/// * `out` - *mut f32
+/// * `len` - usize
+unsafe fn write_embedding(
+    embedding: FaceEmbedding,
+    out: *mut f32,
+    len: usize,
+) -> Result<(), Failure> {
+    if out.is_null() || len != embedding.len() {
+        return Err(Failure::invalid(
+            "the output buffer needs to hold baklava_feature_length values",
+        ));
+    }
+
+    ptr::copy_nonoverlapping(embedding.as_slice().as_ptr(), out, len);
+
+    Ok(())
+}
+
+/// Return the message of the last error which happened on the calling thread, or NULL when no error happened. The
+/// message is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn baklava_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Create a handler loading the model pack at `model`, with `pool_size` sessions. The handler needs to be released
+/// with `baklava_handler_free`.
+///
+/// # Safety
+///
+/// `model` needs to be a nul terminated string and `out` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_handler_new(
+    model: *const c_char,
+    pool_size: usize,
+    out: *mut *mut BaklavaHandler,
+) -> BaklavaStatus {
+    run(|| {
+        if out.is_null() {
+            return Err(Failure::invalid("the output handler is null"));
+        }
+
+        let handler = InsightFace::with_session_pool(read_str(model)?, None, pool_size)?;
+        *out = Box::into_raw(Box::new(BaklavaHandler(handler)));
+
+        Ok(())
+    })
+}
+
+/// Release a handler. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `handler` needs to have been created by `baklava_handler_new` and not be used afterward.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_handler_free(handler: *mut BaklavaHandler) {
+    if !handler.is_null() {
+        drop(Box::from_raw(handler));
+    }
+}
+
+/// Return the number of values of the embeddings extracted by the handler, or 0 when the handler is NULL
+///
+/// # Safety
+///
+/// `handler` needs to be NULL or a handler created by `baklava_handler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_feature_length(handler: *const BaklavaHandler) -> usize {
+    handler
+        .as_ref()
+        .map_or(0, |handler| handler.0.model_info().feature_length)
+}
+
+/// Return the cosine threshold recommended by the loaded model pack, or NaN when the handler is NULL
+///
+/// # Safety
+///
+/// `handler` needs to be NULL or a handler created by `baklava_handler_new`.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_recommended_threshold(handler: *const BaklavaHandler) -> f32 {
+    handler.as_ref().map_or(f32::NAN, |handler| {
+        handler.0.runtime().recommended_threshold()
+    })
+}
+
+/// Extract the embedding of the face of the image at `path` into `out`, which holds `len` values
+///
+/// # Safety
+///
+/// `handler` needs to be a handler created by `baklava_handler_new`, `path` a nul terminated string and `out` a
+/// buffer of `len` floats.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_extract(
+    handler: *const BaklavaHandler,
+    path: *const c_char,
+    out: *mut f32,
+    len: usize,
+) -> BaklavaStatus {
+    run(|| {
+        let embedding = read_handler(handler)?.extract(read_str(path)?)?;
+
+        write_embedding(embedding, out, len)
+    })
+}
+
+/// Extract the embedding of the face of an image given as raw pixels (BGR for 3 channels, gray for 1 channel) into
+/// `out`, which holds `len` values
+///
+/// # Safety
+///
+/// `handler` needs to be a handler created by `baklava_handler_new`, `pixels` a buffer of
+/// `width * height * channels` bytes and `out` a buffer of `len` floats.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_extract_pixels(
+    handler: *const BaklavaHandler,
+    pixels: *const u8,
+    width: i32,
+    height: i32,
+    channels: i32,
+    out: *mut f32,
+    len: usize,
+) -> BaklavaStatus {
+    run(|| {
+        if pixels.is_null() || width <= 0 || height <= 0 {
+            return Err(Failure::invalid("the image is empty"));
+        }
+
+        if channels != 1 && channels != 3 {
+            return Err(Failure::invalid(
+                "only 3 channels or 1 channel are supported",
+            ));
+        }
+
+        let size = FaceImage::data_len(width, height, channels)
+            .ok_or_else(|| Failure::invalid("the image is too large"))?;
+        let handler = read_handler(handler)?;
+        let data = std::slice::from_raw_parts(pixels, size).to_vec();
+        let image = FaceImage::new(width, height, channels, data)?;
+
+        write_embedding(handler.extract_image(&image)?, out, len)
+    })
+}
+
+/// Compare `sources_count` source embeddings, stored one after another in `sources`, against the `target`
+/// embedding. Each embedding holds `len` values. The cosine and the percentage of similarity are written to `cosine`
+/// and `percentage`. `methodology` is one of the `BaklavaMethodology` values.
+///
+/// # Safety
+///
+/// `handler` needs to be a handler created by `baklava_handler_new`, `sources` a buffer of `sources_count * len`
+/// floats, `target` a buffer of `len` floats, `cosine` and `percentage` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_compare(
+    handler: *const BaklavaHandler,
+    sources: *const f32,
+    sources_count: usize,
+    target: *const f32,
+    len: usize,
+    methodology: u32,
+    cosine: *mut f32,
+    percentage: *mut f64,
+) -> BaklavaStatus {
+    run(|| {
+        let handler = read_handler(handler)?;
+        if sources.is_null() || target.is_null() || cosine.is_null() || percentage.is_null() {
+            return Err(Failure::invalid("a pointer is null"));
+        }
+
+        if sources_count == 0 || len == 0 {
+            return Err(Failure::invalid("at least one source is required"));
+        }
+
+        let methodology = read_methodology(methodology)?;
+        let sources_len = sources_count
+            .checked_mul(len)
+            .ok_or_else(|| Failure::invalid("the sources are too large"))?;
+
+        let sources = std::slice::from_raw_parts(sources, sources_len)
+            .chunks(len)
+            .map(|source| FaceEmbedding::new(source.to_vec()))
+            .collect::<Vec<_>>();
+        let target = FaceEmbedding::new(std::slice::from_raw_parts(target, len).to_vec());
+
+        let (result_cosine, result_percentage) = handler.compare(&sources, &target, methodology)?;
+        *cosine = result_cosine;
+        *percentage = result_percentage;
+
+        Ok(())
+    })
+}
+
+/// Return whether the cosine is above the threshold. The threshold recommended by the model pack is used when
+/// `threshold` is NULL. Return false when the handler is NULL.
+///
+/// # Safety
+///
+/// `handler` needs to be NULL or a handler created by `baklava_handler_new` and `threshold` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn baklava_is_similar(
+    handler: *const BaklavaHandler,
+    cosine: f32,
+    threshold: *const f64,
+) -> bool {
+    handler
+        .as_ref()
+        .is_some_and(|handler| handler.0.is_similar(cosine, threshold.as_ref().copied()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        baklava_compare, baklava_extract_pixels, baklava_last_error, read_methodology,
+        BaklavaMethodology, BaklavaStatus,
+    };
+    use baklava::Methodology;
+    use std::ffi::CStr;
+    use std::{fs, ptr};
+
+    #[test]
+    fn expect_null_handler_to_be_rejected() {
+        let (mut cosine, mut percentage) = (0., 0.);
+        let embedding = [0.5f32; 4];
+
+        let status = unsafe {
+            baklava_compare(
+                ptr::null(),
+                embedding.as_ptr(),
+                1,
+                embedding.as_ptr(),
+                embedding.len(),
+                BaklavaMethodology::Mean as u32,
+                &mut cosine,
+                &mut percentage,
+            )
+        };
+
+        assert_eq!(status, BaklavaStatus::InvalidArgument);
+        let message = unsafe { CStr::from_ptr(baklava_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "the handler is null");
+    }
+
+    #[test]
+    fn expect_unknown_methodology_to_be_rejected() {
+        assert!(matches!(
+            read_methodology(BaklavaMethodology::Median as u32),
+            Ok(Methodology::Median)
+        ));
+
+        let failure = read_methodology(2).err().unwrap();
+        assert_eq!(failure.status, BaklavaStatus::InvalidArgument);
+        assert_eq!(failure.message, "the methodology is unknown");
+    }
+
+    #[test]
+    fn expect_invalid_pixels_to_be_rejected_before_being_read() {
+        let pixels = [0u8; 4];
+        let mut out = [0f32; 4];
+
+        for (width, height, channels, message) in [
+            (-2, 2, 1, "the image is empty"),
+            (2, 0, 1, "the image is empty"),
+            (1, 1, 4, "only 3 channels or 1 channel are supported"),
+            (i32::MAX, i32::MAX, 3, "the handler is null"),
+        ] {
+            let status = unsafe {
+                baklava_extract_pixels(
+                    ptr::null(),
+                    pixels.as_ptr(),
+                    width,
+                    height,
+                    channels,
+                    out.as_mut_ptr(),
+                    out.len(),
+                )
+            };
+
+            assert_eq!(status, BaklavaStatus::InvalidArgument);
+            let error = unsafe { CStr::from_ptr(baklava_last_error()) };
+            assert_eq!(error.to_str().unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn expect_header_to_match_the_sources() {
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let mut generated = vec![];
+        cbindgen::generate(crate_dir)
+            .expect("Expect the C header to be generated")
+            .write(&mut generated);
+
+        let committed = fs::read_to_string(format!("{crate_dir}/include/baklava.h")).unwrap();
+        assert!(
+            committed == String::from_utf8(generated).unwrap(),
+            "include/baklava.h is outdated, regenerate it with `cbindgen --config cbindgen.toml --output include/baklava.h`"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Python bindings of baklava. Images are given either as the path of a file or as a numpy array of `uint8` pixels
+//! with the layout of InspireFace bitmaps: `(height, width, 3)` for BGR images (as loaded by OpenCV) and
+//! `(height, width)` or `(height, width, 1)` for gray images.
+//!
+//! ```python
+//! import baklava
+//! import cv2
+//!
+//! handler = baklava.InsightFace("./Megatron", pool_size=2)
+//! source = handler.extract(cv2.imread("./face1_test.png"))
+//! target = handler.extract("./face2_test.png")
+//!
+//! cosine, percentage = handler.compare([source], target, baklava.Methodology.Mean)
+//! ```
+use baklava::error::FFIError;
+use baklava::face::DetectedFace;
+use baklava::{FaceEmbedding, FaceImage, Gallery, GalleryMatch, InsightFace, Methodology};
+use numpy::{PyArray1, PyReadonlyArray1, PyReadonlyArrayDyn, PyUntypedArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    baklava,
+    BaklavaError,
+    PyException,
+    "Error raised by the InspireFace pipeline"
+);
+
+fn to_py_err(err: FFIError<'_>) -> PyErr {
+    BaklavaError::new_err(err.to_string())
+}
+
+/// ImageInput is an image given by Python, either the path of a file or an array of pixels
+#[derive(FromPyObject)]
+enum ImageInput<'py> {
+    Path(String),
+    Pixels(PyReadonlyArrayDyn<'py, u8>),
+}
+
+impl ImageInput<'_> {
+    /// Copy the pixels of the image. Files are loaded by InspireFace.
+    fn to_image(&self) -> PyResult<FaceImage> {
+        match self {
+            Self::Path(path) => FaceImage::from_path(path).map_err(to_py_err),
+            Self::Pixels(array) => {
+                // The iteration follows the logical order, hence non contiguous arrays are copied correctly
+                let data = array.as_array().iter().copied().collect();
+                image_from_pixels(array.shape(), data)
+            }
+        }
+    }
+}
+
+/// Create an image from the shape of a numpy array and its pixels
+///
+/// # Arguments
+///
+/// * `shape` - &[usize]
+/// * `data` - `Vec<u8>`
+fn image_from_pixels(shape: &[usize], data: Vec<u8>) -> PyResult<FaceImage> {
+    let (height, width, channels) =
+        match *shape {
+            [height, width] => (height, width, 1),
+            [height, width, channels] => (height, width, channels),
+            _ => return Err(BaklavaError::new_err(
+                "images need to be arrays of shape (height, width) or (height, width, channels)",
+            )),
+        };
+
+    let dimension = |value: usize| {
+        i32::try_from(value).map_err(|_| BaklavaError::new_err("the image is too large"))
+    };
+
+    FaceImage::new(
+        dimension(width)?,
+        dimension(height)?,
+        dimension(channels)?,
+        data,
+    )
+    .map_err(to_py_err)
+}
+
+/// Methodology used to aggregate the cosines of several sources
+#[pyclass(name = "Methodology", eq, eq_int, frozen)]
+#[derive(Clone, Copy, PartialEq)]
+enum PyMethodology {
+    Mean,
+    Median,
+}
+
+impl From<PyMethodology> for Methodology {
+    fn from(methodology: PyMethodology) -> Self {
+        match methodology {
+            PyMethodology::Mean => Methodology::Mean,
+            PyMethodology::Median => Methodology::Median,
+        }
+    }
+}
+
+/// Embedding of a face, tagged with the model pack which extracted it
+#[pyclass(name = "Embedding", frozen)]
+struct PyEmbedding(FaceEmbedding);
+
+#[pymethods]
+impl PyEmbedding {
+    /// Create an embedding from a feature vector previously extracted by InspireFace. It's not tagged with a model
+    /// pack, hence only its dimension is checked before being compared.
+    #[new]
+    fn new(data: PyReadonlyArray1<'_, f32>) -> Self {
+        Self(FaceEmbedding::new(data.as_array().to_vec()))
+    }
+
+    /// Name of the model pack which extracted the embedding
+    #[getter]
+    fn model(&self) -> Option<String> {
+        self.0.model().map(|model| model.pack.clone())
+    }
+
+    /// Return the feature vector as a numpy array of float32
+    fn to_numpy<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        PyArray1::from_slice(py, self.0.as_slice())
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Embedding(dimension={}, model={:?})",
+            self.0.len(),
+            self.model()
+        )
+    }
+}
+
+/// Face detected in an image
+#[pyclass(name = "Face", get_all, frozen)]
+struct PyFace {
+    track_id: i32,
+    /// Bounding box as (x, y, width, height)
+    rect: (i32, i32, i32, i32),
+    confidence: f32,
+    /// Pose as (roll, yaw, pitch)
+    pose: (f32, f32, f32),
+    /// Left eye, right eye, nose, left & right corners of the mouth as (x, y)
+    landmarks: Vec<(f32, f32)>,
+}
+
+impl From<&DetectedFace> for PyFace {
+    fn from(face: &DetectedFace) -> Self {
+        Self {
+            track_id: face.track_id,
+            rect: (face.rect.x, face.rect.y, face.rect.width, face.rect.height),
+            confidence: face.confidence,
+            pose: (face.pose.roll, face.pose.yaw, face.pose.pitch),
+            landmarks: face
+                .landmarks
+                .iter()
+                .map(|point| (point.x, point.y))
+                .collect(),
+        }
+    }
+}
+
+/// Enrolled face returned by a search in the gallery
+#[pyclass(name = "GalleryMatch", get_all, frozen)]
+struct PyGalleryMatch {
+    id: i64,
+    cosine: f32,
+    percentage: f64,
+}
+
+impl From<GalleryMatch> for PyGalleryMatch {
+    fn from(found: GalleryMatch) -> Self {
+        Self {
+            id: found.id,
+            cosine: found.cosine,
+            percentage: found.percentage,
+        }
+    }
+}
+
+/// Handler running the InspireFace pipeline of baklava. The GIL is released while the SDK runs, hence a handler
+/// with a pool of several sessions can be shared by Python threads.
+#[pyclass(name = "InsightFace", frozen)]
+struct PyInsightFace(InsightFace);
+
+#[pymethods]
+impl PyInsightFace {
+    #[new]
+    #[pyo3(signature = (model, pool_size = 1))]
+    fn new(model: &str, pool_size: usize) -> PyResult<Self> {
+        InsightFace::with_session_pool(model, None, pool_size)
+            .map(Self)
+            .map_err(to_py_err)
+    }
+
+    /// Extract the embedding of the face of an image
+    fn extract(&self, py: Python<'_>, image: ImageInput<'_>) -> PyResult<PyEmbedding> {
+        let image = image.to_image()?;
+
+        py.detach(|| self.0.extract_image(&image))
+            .map(PyEmbedding)
+            .map_err(to_py_err)
+    }
+
+    /// Detect the faces of an image
+    fn detect(&self, py: Python<'_>, image: ImageInput<'_>) -> PyResult<Vec<PyFace>> {
+        let image = image.to_image()?;
+        let faces = py
+            .detach(|| self.0.detect_faces_in_image(&image))
+            .map_err(to_py_err)?;
+
+        Ok(faces.iter().map(PyFace::from).collect())
+    }
+
+    /// Compare the embeddings of the sources against the embedding of the target and return the cosine and the
+    /// percentage of similarity
+    #[pyo3(signature = (sources, target, methodology = PyMethodology::Mean))]
+    fn compare(
+        &self,
+        py: Python<'_>,
+        sources: Vec<PyRef<'_, PyEmbedding>>,
+        target: PyRef<'_, PyEmbedding>,
+        methodology: PyMethodology,
+    ) -> PyResult<(f32, f64)> {
+        let sources = sources
+            .iter()
+            .map(|source| source.0.clone())
+            .collect::<Vec<_>>();
+        let target = target.0.clone();
+
+        py.detach(|| self.0.compare(&sources, &target, methodology.into()))
+            .map_err(to_py_err)
+    }
+
+    /// Return whether the cosine is above the threshold. Default to the threshold recommended by the model pack.
+    #[pyo3(signature = (cosine, threshold = None))]
+    fn is_similar(&self, cosine: f32, threshold: Option<f64>) -> bool {
+        self.0.is_similar(cosine, threshold)
+    }
+
+    /// Name of the loaded model pack
+    #[getter]
+    fn model(&self) -> String {
+        self.0.model_info().pack.clone()
+    }
+
+    /// Cosine threshold recommended by the loaded model pack
+    #[getter]
+    fn recommended_threshold(&self) -> f32 {
+        self.0.runtime().recommended_threshold()
+    }
+}
+
+/// Set of enrolled faces searched by identity. Only one gallery can exist at a time.
+#[pyclass(name = "Gallery")]
+struct PyGallery {
+    gallery: Gallery,
+    handler: Py<PyInsightFace>,
+}
+
+#[pymethods]
+impl PyGallery {
+    #[new]
+    #[pyo3(signature = (handler, search_threshold = None))]
+    fn new(handler: Py<PyInsightFace>, search_threshold: Option<f32>) -> PyResult<Self> {
+        let gallery = Gallery::new(&handler.get().0, search_threshold).map_err(to_py_err)?;
+
+        Ok(Self { gallery, handler })
+    }
+
+    /// Enroll the face of an image or an embedding under an id and return the id
+    fn enroll(&mut self, py: Python<'_>, id: i64, face: FaceInput<'_>) -> PyResult<i64> {
+        let embedding = self.embedding(py, face)?;
+
+        self.gallery
+            .enroll_embedding(id, &embedding)
+            .map_err(to_py_err)
+    }
+
+    /// Remove an enrolled face
+    fn remove(&mut self, id: i64) -> PyResult<()> {
+        self.gallery.remove(id).map_err(to_py_err)
+    }
+
+    /// Search the `top_k` enrolled faces which are the most similar to the face of an image or to an embedding
+    #[pyo3(signature = (face, top_k = 1))]
+    fn search(
+        &mut self,
+        py: Python<'_>,
+        face: FaceInput<'_>,
+        top_k: usize,
+    ) -> PyResult<Vec<PyGalleryMatch>> {
+        let embedding = self.embedding(py, face)?;
+        let matches = self.gallery.search(&embedding, top_k).map_err(to_py_err)?;
+
+        Ok(matches.into_iter().map(PyGalleryMatch::from).collect())
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        self.gallery.len().map_err(to_py_err)
+    }
+}
+
+impl PyGallery {
+    fn embedding(&self, py: Python<'_>, face: FaceInput<'_>) -> PyResult<FaceEmbedding> {
+        match face {
+            FaceInput::Embedding(embedding) => Ok(embedding.0.clone()),
+            FaceInput::Image(image) => {
+                let image = image.to_image()?;
+                let handler = self.handler.get();
+
+                py.detach(|| handler.0.extract_image(&image))
+                    .map_err(to_py_err)
+            }
+        }
+    }
+}
+
+/// FaceInput is a face given either as an embedding or as an image
+#[derive(FromPyObject)]
+enum FaceInput<'py> {
+    Embedding(PyRef<'py, PyEmbedding>),
+    Image(ImageInput<'py>),
+}
+
+#[pymodule]
+#[pyo3(name = "baklava")]
+fn baklava_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BaklavaError", m.py().get_type::<BaklavaError>())?;
+    m.add_class::<PyMethodology>()?;
+    m.add_class::<PyEmbedding>()?;
+    m.add_class::<PyFace>()?;
+    m.add_class::<PyGalleryMatch>()?;
+    m.add_class::<PyInsightFace>()?;
+    m.add_class::<PyGallery>()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::image_from_pixels;
+
+    #[test]
+    fn expect_array_shape_to_be_converted_to_image() {
+        let image = image_from_pixels(&[2, 3, 3], vec![0; 18]).unwrap();
+        assert_eq!((image.width(), image.height(), image.channels()), (3, 2, 3));
+
+        let image = image_from_pixels(&[2, 3], vec![0; 6]).unwrap();
+        assert_eq!(image.channels(), 1);
+
+        assert!(image_from_pixels(&[6], vec![0; 6]).is_err());
+        assert!(image_from_pixels(&[2, 3, 4], vec![0; 24]).is_err());
+        assert!(image_from_pixels(&[1 << 31, 1], vec![0; 6]).is_err());
+    }
+}
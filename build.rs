@@ -17,7 +17,23 @@ fn build_documentation() -> PathBuf {
         .join(out_dir)
 }
 
+#[cfg(feature = "grpc")]
+fn build_protos() {
+    // Use the vendored protoc unless one is provided
+    if env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("Expect protoc to be vendored");
+        env::set_var("PROTOC", protoc);
+    }
+
+    tonic_prost_build::compile_protos("proto/baklava.proto")
+        .expect("Expect the protobuf schema to compile");
+    println!("cargo::rerun-if-changed=proto/baklava.proto");
+}
+
 fn main() {
+    #[cfg(feature = "grpc")]
+    build_protos();
+
     let lib_path = match env::var("DOCS_RS") {
         Ok(_) => build_documentation().to_str().unwrap().to_string(),
         Err(_) => env::var("INSIGHTFACE_PATH").expect("Expect library path to be defined"),
@@ -0,0 +1,46 @@
+//! Calibrate the cosine threshold on a set of labeled pairs.
+//!
+//! Usage: cargo run --example calibrate -- <pairs.csv> [target_far]
+//!
+//! The same calibration is exposed by the `calibrate` subcommand of the `baklava` binary, which is built with the
+//! `cli` feature (`baklava calibrate pairs.csv --target-far 0.001`).
+//!
+//! Each line of the pairs file is `first,second,label` where the label is 1 for a genuine pair and 0 for an
+//! impostor pair.
+use baklava::{InsightFace, LabeledPair};
+
+// Thresholds at which the error rates are reported
+const THRESHOLDS: [f32; 5] = [0.3, 0.4, 0.48, 0.5, 0.6];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let pairs_path = args
+        .next()
+        .ok_or("the path of the pairs file is required")?;
+    let target_far = args
+        .next()
+        .map(|far| far.parse())
+        .transpose()?
+        .unwrap_or(0.001);
+
+    let handler = InsightFace::new("./Megatron", None)?;
+    let pairs = LabeledPair::read_csv(&pairs_path)?;
+    let calibration = handler.calibrate(&pairs)?;
+
+    let eer = calibration.eer();
+    println!("pairs: {}", pairs.len());
+    println!("eer: {:.4} at {:.4}", eer.rate, eer.threshold);
+
+    for threshold in THRESHOLDS {
+        let point = calibration.rates(threshold);
+        println!(
+            "threshold {threshold:.2}: far {:.4} frr {:.4}",
+            point.far, point.frr
+        );
+    }
+
+    let converter = calibration.converter(target_far, *handler.similarity_converter());
+    println!("converter for a far of {target_far}: {converter:?}");
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Run a verification benchmark over an LFW `pairs.txt` or a CSV of labeled pairs.
+//!
+//! Usage: cargo run --release --example verification_benchmark -- <pairs> [images_root]
+use baklava::{InsightFace, LabeledPair};
+
+// False accept rates at which the true accept rate is reported
+const FAR_TARGETS: [f64; 3] = [0.001, 0.01, 0.1];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let pairs_path = args
+        .next()
+        .ok_or("the path of the pairs file is required")?;
+    let images_root = args.next().unwrap_or_else(|| ".".to_string());
+
+    let handler = InsightFace::new("./Megatron", None)?;
+    let pairs = LabeledPair::read_pairs(&pairs_path, &images_root)?;
+    let report = handler.benchmark(&pairs, &FAR_TARGETS)?;
+
+    println!("model: {}", report.model);
+    println!(
+        "pairs: {} ({} failed)",
+        report.pairs,
+        report.failed_pairs.len()
+    );
+    for failed in &report.failed_pairs {
+        println!("  failed: {}", failed.reason);
+    }
+    println!(
+        "accuracy: {:.4} at {:.4}",
+        report.accuracy, report.threshold
+    );
+    println!(
+        "best accuracy: {:.4} at {:.4}",
+        report.best_accuracy.0, report.best_accuracy.1
+    );
+    println!("eer: {:.4} at {:.4}", report.eer.rate, report.eer.threshold);
+
+    for tar in &report.tar_at_far {
+        println!(
+            "tar@far={}: {:.4} at {:.4}",
+            tar.target_far,
+            tar.point.tar(),
+            tar.point.threshold
+        );
+    }
+
+    println!(
+        "throughput: {:.1} images/s, {:.1} pairs/s ({} images failed)",
+        report.images_per_second(),
+        report.pairs_per_second(),
+        report.failed_images
+    );
+
+    Ok(())
+}
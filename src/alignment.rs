@@ -0,0 +1,139 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::face::{self, DetectedFace};
+use crate::ffi_wrapper::{
+    baklava_face_feature_extract_with_alignment_image_cpy, baklava_face_get_alignment_image,
+};
+use crate::handle::{Bitmap, ImageStream};
+use crate::image::FaceImage;
+use crate::{InsightFace, SUCCESS};
+use autocxx::c_long;
+use autocxx::prelude::*;
+
+// Size of the aligned face crops used by the recognizer
+const ALIGNED_FACE_SIZE: i32 = 112;
+
+/// AlignedFace is the aligned 112x112 crop of a detected face. It's the exact input used by the recognizer
+/// to extract the feature of the face.
+#[derive(Debug, Clone)]
+pub struct AlignedFace {
+    pub face: DetectedFace,
+    pub image: FaceImage,
+}
+
+impl InsightFace {
+    /// Detect the face of an image and return its aligned crop. The face is selected the same way as
+    /// `prepare_images` and `prepare_target_image` do.
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::InsightFace;
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let aligned = handler.align_face("./face1_test.png").unwrap();
+    ///
+    /// aligned.image.write_to_file("./face1_aligned.png").unwrap();
+    /// ```
+    pub fn align_face<'a, S: AsRef<str>>(
+        &mut self,
+        img_path: S,
+    ) -> Result<AlignedFace, FFIError<'a>> {
+        face::execute_face_track(
+            self.session,
+            img_path.as_ref(),
+            |session, stream_ptr, data| {
+                let face = face::read_faces(data)?
+                    .first()
+                    .copied()
+                    .ok_or(FFIError::Alignment("no face has been detected"))?;
+
+                let token = face::read_tokens(data)?
+                    .first()
+                    .ok_or(FFIError::Alignment("no face has been detected"))?;
+
+                let bitmap = unsafe {
+                    let mut result = c_long(0);
+                    let img_ptr = baklava_face_get_alignment_image(
+                        session,
+                        stream_ptr,
+                        token as *const _ as *mut _,
+                        &mut result,
+                    );
+
+                    Bitmap::from_result(img_ptr, result).map_err(|_| {
+                        FFIError::Alignment("unable to get the alignment image of the face")
+                    })?
+                };
+
+                Ok(AlignedFace {
+                    face,
+                    image: FaceImage::from_bitmap(&bitmap)?,
+                })
+            },
+        )
+    }
+
+    /// Extract the embedding of an aligned face crop (e.g. exported with `align_face` or produced by another detector)
+    /// without running the face detection again. The crop must be a 112x112 image.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{FaceImage, InsightFace};
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let crop = FaceImage::from_path("./face1_aligned.png").unwrap();
+    /// let embedding = handler.extract_aligned_face(&crop).unwrap();
+    /// ```
+    pub fn extract_aligned_face<'a>(
+        &mut self,
+        image: &FaceImage,
+    ) -> Result<FaceEmbedding, FFIError<'a>> {
+        if image.width() != ALIGNED_FACE_SIZE || image.height() != ALIGNED_FACE_SIZE {
+            return Err(FFIError::Alignment(
+                "the aligned face needs to be a 112x112 image",
+            ));
+        }
+
+        let model = self.model_info();
+        let mut data = vec![0.; model.feature_length];
+        let bitmap = image.to_bitmap()?;
+        let stream = ImageStream::from_bitmap(&bitmap)?;
+
+        unsafe {
+            let res = baklava_face_feature_extract_with_alignment_image_cpy(
+                self.session,
+                stream.as_ptr(),
+                data.as_mut_ptr(),
+                c_int(data.len() as i32),
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Feature);
+            }
+        }
+
+        Ok(FaceEmbedding::with_model(data, model))
+    }
+
+    /// Same as `extract_aligned_face` but load the aligned face crop from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    pub fn extract_aligned_face_from_path<'a, S: AsRef<str>>(
+        &mut self,
+        img_path: S,
+    ) -> Result<FaceEmbedding, FFIError<'a>> {
+        let image = FaceImage::from_path(img_path)?;
+
+        self.extract_aligned_face(&image)
+    }
+}
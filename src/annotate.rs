@@ -0,0 +1,361 @@
+use crate::error::FFIError;
+use crate::face::{DetectedFace, FaceRect};
+use crate::ffi_wrapper::{
+    HColor, HFImageBitmapDrawCircle, HFImageBitmapDrawRect, HFaceRect, HPoint2i,
+};
+use crate::handle::Bitmap;
+use crate::image::FaceImage;
+use crate::{InsightFace, SUCCESS};
+use autocxx::prelude::*;
+
+// Colors of the annotations in RGB
+const BOX_COLOR: [u8; 3] = [0, 255, 0];
+const SELECTED_COLOR: [u8; 3] = [255, 200, 0];
+const LANDMARK_COLOR: [u8; 3] = [255, 0, 0];
+// Size in pixels of a glyph of the label font before scaling
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+
+/// FaceAnnotation is a face to render with `annotate`. The cosine is the similarity the face got against the sources images if any.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceAnnotation {
+    pub face: DetectedFace,
+    pub cosine: Option<f32>,
+    /// Whether the face is the one picked for the comparison, it's highlighted with a thicker box of another color
+    pub selected: bool,
+}
+
+impl From<DetectedFace> for FaceAnnotation {
+    fn from(face: DetectedFace) -> Self {
+        Self {
+            face,
+            cosine: None,
+            selected: false,
+        }
+    }
+}
+
+impl InsightFace {
+    /// Annotate every face of an image and highlight the one picked by `prepare_images` and `prepare_target_image`,
+    /// which helps to understand why a comparison used an unexpected face.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{FaceImage, InsightFace};
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let image = FaceImage::from_path("./group.png").unwrap();
+    ///
+    /// handler.annotate_faces(&image).unwrap().write_to_file("./group_debug.png").unwrap();
+    /// ```
+    pub fn annotate_faces<'a>(&self, image: &FaceImage) -> Result<FaceImage, FFIError<'a>> {
+        let faces = self.detect_faces_in_image(image)?;
+        let selected = self.detect_selected_face(image)?;
+
+        annotate(image, &mark_selected(faces, selected))
+    }
+}
+
+/// Render the bounding box, the five key points, the track id and the cosine of each face onto a copy of the image.
+/// The annotated image can then be written with `FaceImage::write_to_file` or its pixels retrieved with `FaceImage::into_vec`.
+///
+/// # Arguments
+///
+/// * `image` - &FaceImage
+/// * `faces` - &[FaceAnnotation]
+///
+/// # Examples
+/// ```no_run
+/// use baklava::{annotate::{annotate, FaceAnnotation}, FaceImage, InsightFace};
+///
+/// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+/// let faces = handler.detect_faces("./face1_test.png").unwrap();
+///
+/// let image = FaceImage::from_path("./face1_test.png").unwrap();
+/// let annotations = faces.into_iter().map(FaceAnnotation::from).collect::<Vec<_>>();
+///
+/// annotate(&image, &annotations).unwrap().write_to_file("./face1_debug.png").unwrap();
+/// ```
+pub fn annotate<'a>(
+    image: &FaceImage,
+    faces: &[FaceAnnotation],
+) -> Result<FaceImage, FFIError<'a>> {
+    let thickness = (image.width().min(image.height()) / 300).max(1);
+    let bitmap = image.to_bitmap()?;
+    draw_shapes(&bitmap, faces, thickness)?;
+
+    // The bitmap API of InspireFace does not render text, hence the labels are drawn on the pixels directly.
+    let mut annotated = FaceImage::from_bitmap(&bitmap)?;
+    let scale = (image.height() / 200).max(1);
+    for annotation in faces {
+        let label = match annotation.cosine {
+            Some(cosine) => format!("#{} {cosine:.2}", annotation.face.track_id),
+            None => format!("#{}", annotation.face.track_id),
+        };
+
+        let rect = annotation.face.rect;
+        let label_height = GLYPH_HEIGHT * scale + 2;
+        let y = match rect.y - label_height >= 0 {
+            true => rect.y - label_height,
+            false => rect.y + rect.height + 2,
+        };
+
+        let (color, _) = style(annotation, thickness);
+        draw_label(&mut annotated, &label, rect.x, y, scale, color);
+    }
+
+    Ok(annotated)
+}
+
+/// Draw the bounding boxes and the key points of the faces onto the bitmap
+///
+/// # Arguments
+///
+/// * `bitmap` - &Bitmap
+/// * `faces` - &[FaceAnnotation]
+/// * `thickness` - i32
+fn draw_shapes<'a>(
+    bitmap: &Bitmap,
+    faces: &[FaceAnnotation],
+    thickness: i32,
+) -> Result<(), FFIError<'a>> {
+    for annotation in faces {
+        let rect = annotation.face.rect;
+        let (box_color, thickness) = style(annotation, thickness);
+
+        unsafe {
+            let res = HFImageBitmapDrawRect(
+                bitmap.as_ptr(),
+                HFaceRect {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    height: rect.height,
+                },
+                color(box_color),
+                c_int(thickness),
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Bitmap("unable to draw the bounding box"));
+            }
+
+            for landmark in annotation.face.landmarks.iter() {
+                let res = HFImageBitmapDrawCircle(
+                    bitmap.as_ptr(),
+                    HPoint2i {
+                        x: landmark.x.round() as i32,
+                        y: landmark.y.round() as i32,
+                    },
+                    c_int(thickness + 1),
+                    color(LANDMARK_COLOR),
+                    c_int(-1),
+                );
+
+                if res.0 != SUCCESS {
+                    return Err(FFIError::Bitmap("unable to draw the key point"));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark the face which overlaps the most with the selected face. The selected face is added when it has not been
+/// detected among the faces (e.g. when there are more faces than the maximum of the detection).
+///
+/// # Arguments
+///
+/// * `faces` - `Vec<DetectedFace>`
+/// * `selected` - `Option<DetectedFace>`
+fn mark_selected(faces: Vec<DetectedFace>, selected: Option<DetectedFace>) -> Vec<FaceAnnotation> {
+    let mut annotations = faces
+        .into_iter()
+        .map(FaceAnnotation::from)
+        .collect::<Vec<_>>();
+
+    let Some(selected) = selected else {
+        return annotations;
+    };
+
+    let best = annotations
+        .iter_mut()
+        .map(|annotation| (overlap(&annotation.face.rect, &selected.rect), annotation))
+        .filter(|(overlap, _)| *overlap > 0)
+        .max_by_key(|(overlap, _)| *overlap);
+
+    match best {
+        Some((_, annotation)) => annotation.selected = true,
+        None => annotations.push(FaceAnnotation {
+            selected: true,
+            ..FaceAnnotation::from(selected)
+        }),
+    }
+
+    annotations
+}
+
+/// Return the area of the intersection of two bounding boxes
+///
+/// # Arguments
+///
+/// * `a` - &FaceRect
+/// * `b` - &FaceRect
+fn overlap(a: &FaceRect, b: &FaceRect) -> i64 {
+    let width = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let height = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+
+    width.max(0) as i64 * height.max(0) as i64
+}
+
+/// Return the color and the thickness of the box of a face
+///
+/// # Arguments
+///
+/// * `annotation` - &FaceAnnotation
+/// * `thickness` - i32
+fn style(annotation: &FaceAnnotation, thickness: i32) -> ([u8; 3], i32) {
+    match annotation.selected {
+        true => (SELECTED_COLOR, thickness * 2),
+        false => (BOX_COLOR, thickness),
+    }
+}
+
+/// Draw a label with the built-in font. Pixels falling outside of the image are skipped.
+///
+/// # Arguments
+///
+/// * `image` - &mut FaceImage
+/// * `label` - &str
+/// * `x` - i32
+/// * `y` - i32
+/// * `scale` - i32
+/// * `rgb` - [u8; 3]
+fn draw_label(image: &mut FaceImage, label: &str, x: i32, y: i32, scale: i32, rgb: [u8; 3]) {
+    let [r, g, b] = rgb;
+
+    for (idx, glyph) in label.chars().filter_map(glyph).enumerate() {
+        let origin_x = x + idx as i32 * (GLYPH_WIDTH + 1) * scale;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        image.set_pixel(
+                            origin_x + col * scale + dx,
+                            y + row as i32 * scale + dy,
+                            [b, g, r],
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Return the 3x5 glyph of a character of the label. Each row is stored in the 3 lowest bits.
+///
+/// # Arguments
+///
+/// * `c` - char
+fn glyph(c: char) -> Option<[u8; 5]> {
+    let glyph = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        ' ' => [0b000; 5],
+        _ => return None,
+    };
+
+    Some(glyph)
+}
+
+/// Convert an RGB color to the color used by the bitmap API of InspireFace
+///
+/// # Arguments
+///
+/// * `rgb` - [u8; 3]
+fn color(rgb: [u8; 3]) -> HColor {
+    HColor {
+        r: rgb[0] as f32,
+        g: rgb[1] as f32,
+        b: rgb[2] as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_label, mark_selected, BOX_COLOR};
+    use crate::face::{DetectedFace, FacePoint, FacePose, FaceRect};
+    use crate::image::FaceImage;
+
+    fn face(x: i32) -> DetectedFace {
+        DetectedFace {
+            track_id: x,
+            rect: FaceRect {
+                x,
+                y: 0,
+                width: 10,
+                height: 10,
+            },
+            confidence: 1.,
+            pose: FacePose::default(),
+            landmarks: [FacePoint::default(); 5],
+        }
+    }
+
+    #[test]
+    fn expect_label_to_be_drawn_within_the_image() {
+        let mut image = FaceImage::new(20, 10, 3, vec![0; 20 * 10 * 3]).unwrap();
+
+        // The label overflows the image on purpose
+        draw_label(&mut image, "#12 0.98", 2, 2, 1, BOX_COLOR);
+
+        assert!(image.as_bytes().contains(&255));
+    }
+
+    #[test]
+    fn expect_the_most_overlapping_face_to_be_selected() {
+        let selected = |annotations: Vec<super::FaceAnnotation>| {
+            annotations
+                .iter()
+                .map(|annotation| (annotation.face.track_id, annotation.selected))
+                .collect::<Vec<_>>()
+        };
+
+        let faces = vec![face(0), face(20), face(40)];
+        assert_eq!(
+            selected(mark_selected(faces.clone(), Some(face(22)))),
+            [(0, false), (20, true), (40, false)]
+        );
+        assert_eq!(
+            selected(mark_selected(faces.clone(), None)),
+            [(0, false), (20, false), (40, false)]
+        );
+
+        // The selected face is kept even when it has not been detected
+        assert_eq!(
+            selected(mark_selected(faces, Some(face(80)))),
+            [(0, false), (20, false), (40, false), (80, true)]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::face::DetectedFace;
+use crate::image::FaceImage;
+use crate::{InsightFace, Methodology};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// AsyncInsightFace is an asynchronous facade over a shared InsightFace handler for tokio based services.
+/// Each call is dispatched to the blocking pool of tokio and awaited.
+///
+/// At most `max_in_flight` calls are dispatched at the same time. Further calls wait for a slot before being
+/// dispatched, which applies backpressure onto the callers instead of piling up work in the blocking pool. The calls
+/// run concurrently on the sessions of the pool of the handler, hence `max_in_flight` is best set to its pool size.
+///
+/// Dropping the future of a call (e.g. with `tokio::time::timeout` or `tokio::select!`) cancels it. A call which
+/// has not started yet is skipped. A call already running in the SDK can not be interrupted, its result is discarded.
+#[derive(Clone)]
+pub struct AsyncInsightFace {
+    handler: Arc<InsightFace>,
+    permits: Arc<Semaphore>,
+}
+
+impl AsyncInsightFace {
+    /// Create a new facade owning the handler
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - InsightFace
+    /// * `max_in_flight` - usize
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{AsyncInsightFace, InsightFace, Methodology};
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = AsyncInsightFace::new(InsightFace::with_session_pool("./Megatron", None, 4)?, 4);
+    ///
+    /// let (cosine, percentage) = handler
+    ///     .compare(vec!["./face1_test.png".to_string()], "./face2_test.png".to_string(), Methodology::Mean)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(handler: InsightFace, max_in_flight: usize) -> Self {
+        Self::from_shared(Arc::new(handler), max_in_flight)
+    }
+
+    /// Create a new facade over a handler which is already shared with synchronous code
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - `Arc<InsightFace>`
+    /// * `max_in_flight` - usize
+    pub fn from_shared(handler: Arc<InsightFace>, max_in_flight: usize) -> Self {
+        Self {
+            handler,
+            permits: Arc::new(Semaphore::new(max_in_flight.max(1))),
+        }
+    }
+
+    /// Return the number of calls which can be dispatched without waiting
+    pub fn available_slots(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// Return the shared handler
+    pub fn handler(&self) -> &Arc<InsightFace> {
+        &self.handler
+    }
+
+    /// Compare the sources images against the target image
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - `Vec<String>`
+    /// * `target` - String
+    /// * `methodology` - Methodology
+    pub async fn compare(
+        &self,
+        sources: Vec<String>,
+        target: String,
+        methodology: Methodology,
+    ) -> Result<(f32, f64), FFIError<'static>> {
+        self.run(move |handler| {
+            let sources = sources
+                .iter()
+                .map(|source| handler.extract(source))
+                .collect::<Result<Vec<_>, _>>()?;
+            let target = handler.extract(target)?;
+
+            handler.compare(&sources, &target, methodology)
+        })
+        .await
+    }
+
+    /// Extract the embedding of the face of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - String
+    pub async fn extract_embedding(
+        &self,
+        img_path: String,
+    ) -> Result<FaceEmbedding, FFIError<'static>> {
+        self.run(move |handler| handler.extract(img_path)).await
+    }
+
+    /// Detect the faces of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - String
+    pub async fn detect_faces(
+        &self,
+        img_path: String,
+    ) -> Result<Vec<DetectedFace>, FFIError<'static>> {
+        self.run(move |handler| handler.detect_faces_in_image(&FaceImage::from_path(img_path)?))
+            .await
+    }
+
+    /// Run a job with the shared handler on the blocking pool. The error of the job can be any type built from an
+    /// `FFIError`, e.g. the error type of a service.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - F
+    pub async fn run<T, E, F>(&self, job: F) -> Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<FFIError<'static>> + Send + 'static,
+        F: FnOnce(&InsightFace) -> Result<T, E> + Send + 'static,
+    {
+        // Waiting for a permit is where the backpressure is applied
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| FFIError::Async("the facade has been closed"))?;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(cancelled.clone());
+        let handler = self.handler.clone();
+
+        let task = tokio::task::spawn_blocking(move || {
+            // The permit is held until the SDK is done, even if the caller went away
+            let _permit = permit;
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(FFIError::Async("the call has been cancelled").into());
+            }
+
+            job(&handler)
+        });
+
+        task.await
+            .map_err(|_| FFIError::Async("the blocking task panicked"))?
+    }
+}
+
+/// CancelOnDrop flags the call as cancelled when the future awaiting it is dropped
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::calibration::{Calibration, EqualErrorRate, LabeledPair, OperatingPoint};
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::model::ModelInfo;
+use crate::{InsightFace, Methodology};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Extension of the images of the LFW dataset
+const LFW_EXTENSION: &str = "jpg";
+
+impl LabeledPair {
+    /// Read the pairs of a file. Files ending with `.csv` are read with `read_csv`, the other ones are read as an LFW
+    /// `pairs.txt` whose images are in `images_root`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - P
+    /// * `images_root` - R
+    pub fn read_pairs<'a, P: AsRef<Path>, R: AsRef<Path>>(
+        path: P,
+        images_root: R,
+    ) -> Result<Vec<Self>, FFIError<'a>> {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::read_csv(path),
+            _ => Self::read_lfw(path, images_root),
+        }
+    }
+
+    /// Read the pairs of an LFW `pairs.txt`. A genuine pair is written `name n1 n2` and an impostor pair
+    /// `name1 n1 name2 n2`, the image `n` of `name` being `images_root/name/name_000n.jpg`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - P
+    /// * `images_root` - R
+    pub fn read_lfw<'a, P: AsRef<Path>, R: AsRef<Path>>(
+        path: P,
+        images_root: R,
+    ) -> Result<Vec<Self>, FFIError<'a>> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| FFIError::Benchmark("unable to read the pairs file"))?;
+
+        Self::parse_lfw(&content, images_root)
+    }
+
+    /// Parse the pairs of an LFW `pairs.txt` content. See `read_lfw` for the format. The first line is skipped when
+    /// it's the header giving the number of folds and pairs, any other line needs to be a pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - &str
+    /// * `images_root` - R
+    pub fn parse_lfw<'a, R: AsRef<Path>>(
+        content: &str,
+        images_root: R,
+    ) -> Result<Vec<Self>, FFIError<'a>> {
+        let image = |name: &str, number: &str| -> Result<String, FFIError<'a>> {
+            let number = number
+                .parse::<u32>()
+                .map_err(|_| FFIError::Benchmark("the image number is not a number"))?;
+
+            Ok(images_root
+                .as_ref()
+                .join(name)
+                .join(format!("{name}_{number:04}.{LFW_EXTENSION}"))
+                .to_string_lossy()
+                .into_owned())
+        };
+
+        content
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|columns| !columns.is_empty())
+            .enumerate()
+            // The header only holds the number of folds and pairs, or the number of pairs for the dev views
+            .filter(|(line, columns)| *line > 0 || columns.len() > 2)
+            .map(|(_, columns)| match columns[..] {
+                [name, first, second] => Ok(Self {
+                    first: image(name, first)?,
+                    second: image(name, second)?,
+                    genuine: true,
+                }),
+                [first_name, first, second_name, second] => Ok(Self {
+                    first: image(first_name, first)?,
+                    second: image(second_name, second)?,
+                    genuine: false,
+                }),
+                _ => Err(FFIError::Benchmark(
+                    "each line needs to have either 3 or 4 columns",
+                )),
+            })
+            .collect()
+    }
+}
+
+/// TarAtFar is the true accept rate reached at a target false accept rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TarAtFar {
+    pub target_far: f64,
+    /// Operating point with the lowest threshold whose false accept rate does not exceed the target
+    pub point: OperatingPoint,
+}
+
+/// FailedPair is a pair left out of a benchmark as a face could not be extracted from one of its images
+#[derive(Debug, Clone)]
+pub struct FailedPair {
+    pub pair: LabeledPair,
+    /// Image which failed along with the error, e.g. a missing file or no detected face
+    pub reason: String,
+}
+
+/// BenchmarkReport is the outcome of a verification benchmark
+#[derive(Debug, Clone)]
+pub struct BenchmarkReport {
+    pub model: ModelInfo,
+    /// Number of pairs which have been compared
+    pub pairs: usize,
+    /// Pairs skipped as a face could not be extracted from one of their images
+    pub failed_pairs: Vec<FailedPair>,
+    /// Accuracy at the threshold used by `is_similar`
+    pub accuracy: f64,
+    pub threshold: f32,
+    /// Highest accuracy reachable with a single threshold, along with the threshold
+    pub best_accuracy: (f64, f32),
+    pub eer: EqualErrorRate,
+    pub tar_at_far: Vec<TarAtFar>,
+    /// Time spent extracting and comparing the embeddings
+    pub elapsed: Duration,
+    /// Number of images whose embedding has been extracted, each image being extracted once
+    pub images: usize,
+    /// Number of images where no embedding could be extracted
+    pub failed_images: usize,
+}
+
+impl BenchmarkReport {
+    /// Return the number of images extracted per second. The images which failed are not counted.
+    pub fn images_per_second(&self) -> f64 {
+        self.images as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Return the number of pairs compared per second
+    pub fn pairs_per_second(&self) -> f64 {
+        self.pairs as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl InsightFace {
+    /// Run a verification benchmark over labeled pairs with the pipeline of the handler. Pairs where a face can't be
+    /// extracted are reported as failed, with the reason, and left out of the metrics. When no pair can be compared,
+    /// the error of the first image which failed is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - `&[LabeledPair]`
+    /// * `far_targets` - `&[f64]` the false accept rates at which the true accept rate is reported
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{InsightFace, LabeledPair};
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let pairs = LabeledPair::read_pairs("./lfw/pairs.txt", "./lfw/images").unwrap();
+    ///
+    /// let report = handler.benchmark(&pairs, &[0.001, 0.01]).unwrap();
+    /// println!("accuracy: {:.4}", report.accuracy);
+    /// ```
+    pub fn benchmark<'a>(
+        &self,
+        pairs: &[LabeledPair],
+        far_targets: &[f64],
+    ) -> Result<BenchmarkReport, FFIError<'a>> {
+        let start = Instant::now();
+        // Images which failed keep the reason to report it with each of their pairs
+        let mut embeddings: HashMap<&str, Result<FaceEmbedding, String>> = HashMap::new();
+        let mut first_error = None;
+        let mut scores = Vec::with_capacity(pairs.len());
+        let mut failed_pairs = vec![];
+
+        for pair in pairs {
+            for path in [pair.first.as_str(), pair.second.as_str()] {
+                if !embeddings.contains_key(path) {
+                    let embedding = self.extract(path).map_err(|err| {
+                        let reason = format!("{path}: {err}");
+                        first_error.get_or_insert(err);
+                        reason
+                    });
+                    embeddings.insert(path, embedding);
+                }
+            }
+
+            let (first, second) = match (
+                &embeddings[pair.first.as_str()],
+                &embeddings[pair.second.as_str()],
+            ) {
+                (Ok(first), Ok(second)) => (first, second),
+                (Err(reason), _) | (_, Err(reason)) => {
+                    failed_pairs.push(FailedPair {
+                        pair: pair.clone(),
+                        reason: reason.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            let (cosine, _) =
+                self.compare(std::slice::from_ref(first), second, Methodology::Mean)?;
+            scores.push((cosine, pair.genuine));
+        }
+
+        if let (true, Some(err)) = (scores.is_empty(), first_error) {
+            return Err(err);
+        }
+
+        let elapsed = start.elapsed();
+        let calibration = Calibration::from_scores(scores)?;
+
+        Ok(BenchmarkReport {
+            model: self.model_info().as_ref().clone(),
+            pairs: calibration.scores().len(),
+            failed_pairs,
+            accuracy: calibration.accuracy(self.threshold),
+            threshold: self.threshold,
+            best_accuracy: calibration.best_accuracy(),
+            eer: calibration.eer(),
+            tar_at_far: far_targets
+                .iter()
+                .map(|target_far| TarAtFar {
+                    target_far: *target_far,
+                    point: calibration.threshold_for_far(*target_far),
+                })
+                .collect(),
+            elapsed,
+            images: embeddings
+                .values()
+                .filter(|embedding| embedding.is_ok())
+                .count(),
+            failed_images: embeddings
+                .values()
+                .filter(|embedding| embedding.is_err())
+                .count(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calibration::LabeledPair;
+    use std::path::Path;
+
+    #[test]
+    fn expect_lfw_pairs_to_be_parsed() {
+        let root = Path::new("lfw");
+        let pairs = LabeledPair::parse_lfw(
+            "10\t300\nAbel_Pacheco\t1\t4\nAbdel_Madi_Shabneh\t1\tDean_Barker\t1\n",
+            root,
+        )
+        .unwrap();
+
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs[0].genuine);
+        assert_eq!(
+            Path::new(&pairs[0].second),
+            root.join("Abel_Pacheco").join("Abel_Pacheco_0004.jpg")
+        );
+        assert!(!pairs[1].genuine);
+        assert_eq!(
+            Path::new(&pairs[1].second),
+            root.join("Dean_Barker").join("Dean_Barker_0001.jpg")
+        );
+
+        assert!(LabeledPair::parse_lfw("Abel_Pacheco\t1\tfour", root).is_err());
+        assert!(LabeledPair::parse_lfw("a\t1\tb\t2\t3", root).is_err());
+
+        // Only the first line can be a header, a truncated pair is an error
+        let pairs = LabeledPair::parse_lfw("300\nAbel_Pacheco\t1\t4\n", root).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert!(LabeledPair::parse_lfw("10\t300\nAbel_Pacheco\t1\n", root).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Batch comparison of a directory of identities. Each folder of the directory holds the images of one person.
+//! The images are enrolled into a template per identity, then every probe image is compared against its claimed
+//! identity and, optionally, against every identity. Each comparison and each failure is written as a row of a CSV
+//! or JSONL report.
+use baklava::{read_identities, InsightFace, Methodology};
+use clap::{Args, ValueEnum};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+const CSV_HEADER: &str = "stage,image,claimed,identity,cosine,percentage,decision,genuine,error";
+
+#[derive(Args)]
+pub(crate) struct BatchArgs {
+    /// Directory with one folder of images per identity
+    identities: PathBuf,
+    /// Directory of the probes with one folder per claimed identity. Without it, the images of each identity which
+    /// are not enrolled are used as probes.
+    #[arg(long)]
+    probes: Option<PathBuf>,
+    /// Number of images enrolled per identity when the probes come from the identities directory
+    #[arg(long, default_value_t = NonZeroUsize::MIN)]
+    enroll: NonZeroUsize,
+    #[arg(long, value_enum, default_value_t = Mode::Claimed)]
+    mode: Mode,
+    /// Cosine above which a probe matches an identity. Default to the one recommended by the model pack.
+    #[arg(long)]
+    threshold: Option<f64>,
+    /// File where the report is written
+    #[arg(short, long)]
+    output: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Compare each probe against its claimed identity
+    Claimed,
+    /// Compare each probe against every identity
+    All,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+/// Stage of the batch a row of the report comes from
+#[derive(Clone, Copy)]
+enum Stage {
+    Enroll,
+    Probe,
+}
+
+impl Stage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Enroll => "enroll",
+            Stage::Probe => "probe",
+        }
+    }
+}
+
+/// Row of the report. A row either holds the outcome of a comparison or the reason of a failure.
+struct Row {
+    stage: Stage,
+    image: String,
+    claimed: String,
+    identity: Option<String>,
+    comparison: Option<(f32, f64, bool)>,
+    error: Option<String>,
+}
+
+impl Row {
+    fn failure<E: ToString>(stage: Stage, image: &Path, claimed: &str, error: E) -> Self {
+        Self {
+            stage,
+            image: image.display().to_string(),
+            claimed: claimed.to_string(),
+            identity: None,
+            comparison: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// Return whether the compared identity is the claimed one, hence whether a match is expected
+    fn genuine(&self) -> Option<bool> {
+        self.identity
+            .as_ref()
+            .map(|identity| *identity == self.claimed)
+    }
+
+    fn to_csv(&self) -> String {
+        let (cosine, percentage, decision) = match self.comparison {
+            Some((cosine, percentage, decision)) => (
+                cosine.to_string(),
+                percentage.to_string(),
+                decision_label(decision).to_string(),
+            ),
+            None => Default::default(),
+        };
+        let genuine = self
+            .genuine()
+            .map(|genuine| genuine.to_string())
+            .unwrap_or_default();
+
+        [
+            self.stage.as_str(),
+            &self.image,
+            &self.claimed,
+            self.identity.as_deref().unwrap_or_default(),
+            &cosine,
+            &percentage,
+            &decision,
+            &genuine,
+            self.error.as_deref().unwrap_or_default(),
+        ]
+        .map(escape_csv)
+        .join(",")
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "stage": self.stage.as_str(),
+            "image": self.image,
+            "claimed": self.claimed,
+            "identity": self.identity,
+            "cosine": self.comparison.map(|(cosine, _, _)| cosine),
+            "percentage": self.comparison.map(|(_, percentage, _)| percentage),
+            "decision": self.comparison.map(|(_, _, decision)| decision_label(decision)),
+            "genuine": self.genuine(),
+            "error": self.error,
+        })
+    }
+}
+
+/// Report writes the rows as they are produced and counts them
+struct Report {
+    writer: BufWriter<File>,
+    format: Format,
+    comparisons: usize,
+    matches: usize,
+    // Matches against another identity than the claimed one
+    false_accepts: usize,
+    // Non matches against the claimed identity
+    false_rejects: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn create(path: &Path, format: Format) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == Format::Csv {
+            writeln!(writer, "{CSV_HEADER}")?;
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            comparisons: 0,
+            matches: 0,
+            false_accepts: 0,
+            false_rejects: 0,
+            failures: 0,
+        })
+    }
+
+    fn write(&mut self, row: Row) -> Result<(), Box<dyn Error>> {
+        match row.comparison {
+            Some((_, _, decision)) => {
+                let genuine = row.genuine().unwrap_or_default();
+                self.comparisons += 1;
+                self.matches += decision as usize;
+                self.false_accepts += (decision && !genuine) as usize;
+                self.false_rejects += (!decision && genuine) as usize;
+            }
+            None => self.failures += 1,
+        }
+
+        match self.format {
+            Format::Csv => writeln!(self.writer, "{}", row.to_csv())?,
+            Format::Jsonl => writeln!(self.writer, "{}", row.to_json())?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the batch and return the human and the JSON summaries
+///
+/// # Arguments
+///
+/// * `handler` - &InsightFace
+/// * `args` - &BatchArgs
+pub(crate) fn run(
+    handler: &InsightFace,
+    args: &BatchArgs,
+) -> Result<(String, Value), Box<dyn Error>> {
+    let identities = read_identities(&args.identities)?;
+    let mut report = Report::create(&args.output, args.format)?;
+
+    // Enroll each identity and collect the probes
+    let mut templates = BTreeMap::new();
+    let mut probes = vec![];
+    for identity in &identities {
+        let (name, images) = (&identity.name, &identity.images);
+        let enrolled = match args.probes {
+            Some(_) => images.len(),
+            None => args.enroll.get().min(images.len()),
+        };
+
+        let enrollment = handler.enroll_identity(&images[..enrolled]);
+        for (image, err) in enrollment.skipped {
+            report.write(Row::failure(Stage::Enroll, &image, name, err))?;
+        }
+
+        match enrollment.template {
+            Ok(template) => {
+                templates.insert(name.clone(), template);
+            }
+            Err(err) => report.write(Row::failure(Stage::Enroll, &identity.path, name, err))?,
+        }
+
+        if args.probes.is_none() {
+            probes.extend(
+                images[enrolled..]
+                    .iter()
+                    .map(|image| (name.clone(), image.clone())),
+            );
+        }
+    }
+
+    if let Some(dir) = &args.probes {
+        for identity in read_identities(dir)? {
+            let name = identity.name;
+            probes.extend(
+                identity
+                    .images
+                    .into_iter()
+                    .map(|image| (name.clone(), image)),
+            );
+        }
+    }
+
+    for (claimed, image) in &probes {
+        let probe = match handler.extract(image.to_string_lossy()) {
+            Ok(probe) => probe,
+            Err(err) => {
+                report.write(Row::failure(Stage::Probe, image, claimed, err))?;
+                continue;
+            }
+        };
+
+        let targets = match args.mode {
+            Mode::Claimed => templates.get_key_value(claimed).into_iter().collect(),
+            Mode::All => templates.iter().collect::<Vec<_>>(),
+        };
+
+        if targets.is_empty() {
+            report.write(Row::failure(
+                Stage::Probe,
+                image,
+                claimed,
+                "the claimed identity has not been enrolled",
+            ))?;
+            continue;
+        }
+
+        for (identity, template) in targets {
+            let row = match handler.compare(
+                std::slice::from_ref(template.embedding()),
+                &probe,
+                Methodology::Mean,
+            ) {
+                Ok((cosine, percentage)) => Row {
+                    stage: Stage::Probe,
+                    image: image.display().to_string(),
+                    claimed: claimed.clone(),
+                    identity: Some(identity.clone()),
+                    comparison: Some((
+                        cosine,
+                        percentage,
+                        handler.is_similar(cosine, args.threshold),
+                    )),
+                    error: None,
+                },
+                Err(err) => Row::failure(Stage::Probe, image, claimed, err),
+            };
+
+            report.write(row)?;
+        }
+    }
+
+    report.writer.flush()?;
+
+    Ok((
+        format!(
+            "identities: {}\nenrolled: {}\nprobes: {}\ncomparisons: {}\nmatches: {}\nfalse accepts: {}\nfalse rejects: {}\nfailures: {}\nreport: {}",
+            identities.len(),
+            templates.len(),
+            probes.len(),
+            report.comparisons,
+            report.matches,
+            report.false_accepts,
+            report.false_rejects,
+            report.failures,
+            args.output.display()
+        ),
+        json!({
+            "identities": identities.len(),
+            "enrolled": templates.len(),
+            "probes": probes.len(),
+            "comparisons": report.comparisons,
+            "matches": report.matches,
+            "false_accepts": report.false_accepts,
+            "false_rejects": report.false_rejects,
+            "failures": report.failures,
+            "report": args.output,
+        }),
+    ))
+}
+
+fn decision_label(decision: bool) -> &'static str {
+    match decision {
+        true => "match",
+        false => "non_match",
+    }
+}
+
+/// Quote a CSV field when it holds a separator, a quote or a line break
+fn escape_csv(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_csv, Row, Stage};
+
+    #[test]
+    fn expect_csv_fields_to_be_escaped() {
+        assert_eq!(escape_csv("face.png"), "face.png");
+        assert_eq!(escape_csv("a,b.png"), "\"a,b.png\"");
+        assert_eq!(
+            escape_csv("Unable to \"track\""),
+            "\"Unable to \"\"track\"\"\""
+        );
+    }
+
+    #[test]
+    fn expect_comparisons_to_tell_whether_they_are_genuine() {
+        let row = |identity: &str, decision: bool| Row {
+            stage: Stage::Probe,
+            image: "bob/2.png".to_string(),
+            claimed: "bob".to_string(),
+            identity: Some(identity.to_string()),
+            comparison: Some((0.5, 75., decision)),
+            error: None,
+        };
+
+        assert_eq!(
+            row("bob", true).to_csv(),
+            "probe,bob/2.png,bob,bob,0.5,75,match,true,"
+        );
+        assert_eq!(
+            row("alice", true).to_csv(),
+            "probe,bob/2.png,bob,alice,0.5,75,match,false,"
+        );
+        assert_eq!(row("alice", false).to_json()["genuine"], false);
+
+        let failure = Row::failure(Stage::Probe, "bob/3.png".as_ref(), "bob", "no face");
+        assert_eq!(failure.genuine(), None);
+        assert_eq!(failure.to_csv(), "probe,bob/3.png,bob,,,,,,no face");
+    }
+}
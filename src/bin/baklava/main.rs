@@ -0,0 +1,327 @@
+//! Command line interface of baklava to run one-off comparisons, detections and embedding extractions, and batches
+//! over directories of identities.
+//!
+//! Every subcommand prints a human readable output by default and a JSON document with `--json`.
+use baklava::benchmark::BenchmarkReport;
+use baklava::calibration::Calibration;
+use baklava::face::DetectedFace;
+use baklava::{InsightFace, LabeledPair, Methodology, SimilarityConverter};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+mod batch;
+
+#[derive(Parser)]
+#[command(name = "baklava", version, about = "Face comparison with InspireFace")]
+struct Cli {
+    /// Path of the model pack
+    #[arg(long, global = true, default_value = "./Megatron")]
+    model: String,
+    /// Print the output as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare the faces of the sources images against the face of the target image
+    Compare {
+        /// Images the target is compared against
+        #[arg(long, num_args = 1.., required = true)]
+        sources: Vec<String>,
+        /// Image compared against the sources
+        #[arg(long)]
+        target: String,
+        #[arg(long, value_enum, default_value_t = Method::Mean)]
+        method: Method,
+        /// Cosine above which the faces are similar. Default to the one recommended by the model pack.
+        #[arg(long)]
+        threshold: Option<f64>,
+    },
+    /// Detect the faces of an image
+    Detect { image: String },
+    /// Extract the embedding of the face of an image
+    Embed {
+        image: String,
+        /// File where the embedding is written as little endian f32
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the information of the model pack
+    Info,
+    /// Calibrate the cosine threshold on labeled pairs (CSV of first,second,label)
+    Calibrate {
+        pairs: PathBuf,
+        /// False accept rate the threshold needs to reach
+        #[arg(long, default_value_t = 0.001)]
+        target_far: f64,
+    },
+    /// Run a verification benchmark over an LFW pairs.txt or a CSV of labeled pairs
+    Benchmark {
+        pairs: PathBuf,
+        /// Directory of the images of an LFW pairs.txt
+        #[arg(long, default_value = ".")]
+        images_root: PathBuf,
+        /// False accept rates at which the true accept rate is reported
+        #[arg(long, num_args = 1.., default_values_t = [0.001, 0.01, 0.1])]
+        far: Vec<f64>,
+    },
+    /// Enroll a directory of identities (one folder per person) and compare every probe against them
+    Batch(batch::BatchArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    Mean,
+    Median,
+}
+
+impl From<Method> for Methodology {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Mean => Methodology::Mean,
+            Method::Median => Methodology::Median,
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let mut handler = InsightFace::new(&cli.model, None)?;
+
+    let (human, document) = match cli.command {
+        Command::Compare {
+            sources,
+            target,
+            method,
+            threshold,
+        } => {
+            let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+            let (cosine, percentage) = handler
+                .prepare_images(&sources)?
+                .prepare_target_image(&target)?
+                .compare_images(method.into())?;
+            let similar = handler.is_similar(cosine, threshold);
+
+            (
+                format!("cosine: {cosine}\npercentage: {percentage}\nsimilar: {similar}"),
+                json!({ "cosine": cosine, "percentage": percentage, "similar": similar }),
+            )
+        }
+        Command::Detect { image } => {
+            let faces = handler.detect_faces(&image)?;
+            let human = faces
+                .iter()
+                .map(|face| {
+                    format!(
+                        "face {}: x {} y {} width {} height {} confidence {:.3}",
+                        face.track_id,
+                        face.rect.x,
+                        face.rect.y,
+                        face.rect.width,
+                        face.rect.height,
+                        face.confidence
+                    )
+                })
+                .chain([format!("faces: {}", faces.len())])
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            (
+                human,
+                json!({ "faces": faces.iter().map(face_to_json).collect::<Vec<_>>() }),
+            )
+        }
+        Command::Embed { image, output } => {
+            let embedding = handler.extract(&image)?;
+            match output {
+                Some(output) => {
+                    let bytes = embedding
+                        .as_slice()
+                        .iter()
+                        .flat_map(|value| value.to_le_bytes())
+                        .collect::<Vec<_>>();
+                    fs::write(&output, bytes)?;
+
+                    (
+                        format!(
+                            "embedding of {} dimensions written to {}",
+                            embedding.len(),
+                            output.display()
+                        ),
+                        json!({ "dimension": embedding.len(), "output": output }),
+                    )
+                }
+                None => (
+                    format!("{:?}", embedding.as_slice()),
+                    json!({ "dimension": embedding.len(), "embedding": embedding.as_slice() }),
+                ),
+            }
+        }
+        Command::Info => {
+            let info = handler.model_info();
+            let converter = handler.similarity_converter();
+            let threshold = handler.runtime().recommended_threshold();
+
+            (
+                format!(
+                    "pack: {}\ninspireface: {}\nfeature length: {}\nrecommended threshold: {}\ninformation: {}",
+                    info.pack, info.version, info.feature_length, threshold, info.information
+                ),
+                json!({
+                    "pack": info.pack,
+                    "version": info.version.to_string(),
+                    "feature_length": info.feature_length,
+                    "information": info.information,
+                    "recommended_threshold": threshold,
+                    "similarity_converter": converter_to_json(converter),
+                }),
+            )
+        }
+        Command::Calibrate { pairs, target_far } => {
+            let calibration = handler.calibrate(&LabeledPair::read_csv(pairs)?)?;
+            calibration_output(&calibration, target_far, &handler)
+        }
+        Command::Benchmark {
+            pairs,
+            images_root,
+            far,
+        } => {
+            let pairs = LabeledPair::read_pairs(pairs, images_root)?;
+            benchmark_output(&handler.benchmark(&pairs, &far)?)
+        }
+        Command::Batch(args) => batch::run(&handler, &args)?,
+    };
+
+    match cli.json {
+        true => println!("{}", serde_json::to_string_pretty(&document)?),
+        false => println!("{human}"),
+    }
+
+    Ok(())
+}
+
+fn face_to_json(face: &DetectedFace) -> Value {
+    json!({
+        "track_id": face.track_id,
+        "rect": {
+            "x": face.rect.x,
+            "y": face.rect.y,
+            "width": face.rect.width,
+            "height": face.rect.height,
+        },
+        "confidence": face.confidence,
+        "pose": {
+            "roll": face.pose.roll,
+            "yaw": face.pose.yaw,
+            "pitch": face.pose.pitch,
+        },
+        "landmarks": face.landmarks.iter().map(|point| [point.x, point.y]).collect::<Vec<_>>(),
+    })
+}
+
+fn converter_to_json(converter: &SimilarityConverter) -> Value {
+    json!({
+        "threshold": converter.threshold,
+        "middle_score": converter.middle_score,
+        "steepness": converter.steepness,
+        "output_min": converter.output_min,
+        "output_max": converter.output_max,
+    })
+}
+
+fn calibration_output(
+    calibration: &Calibration,
+    target_far: f64,
+    handler: &InsightFace,
+) -> (String, Value) {
+    let eer = calibration.eer();
+    let point = calibration.threshold_for_far(target_far);
+    let converter = calibration.converter(target_far, *handler.similarity_converter());
+
+    (
+        format!(
+            "pairs: {}\neer: {:.4} at {:.4}\nthreshold for a far of {target_far}: {:.4} (frr {:.4})\nconverter: {converter:?}",
+            calibration.scores().len(),
+            eer.rate,
+            eer.threshold,
+            point.threshold,
+            point.frr
+        ),
+        json!({
+            "pairs": calibration.scores().len(),
+            "eer": { "rate": eer.rate, "threshold": eer.threshold },
+            "target_far": target_far,
+            "threshold": point.threshold,
+            "far": point.far,
+            "frr": point.frr,
+            "curve": calibration
+                .curve()
+                .iter()
+                .map(|point| json!({ "threshold": point.threshold, "far": point.far, "frr": point.frr }))
+                .collect::<Vec<_>>(),
+            "similarity_converter": converter_to_json(&converter),
+        }),
+    )
+}
+
+fn benchmark_output(report: &BenchmarkReport) -> (String, Value) {
+    let tar_at_far = report
+        .tar_at_far
+        .iter()
+        .map(|tar| {
+            format!(
+                "tar@far={}: {:.4} at {:.4}",
+                tar.target_far,
+                tar.point.tar(),
+                tar.point.threshold
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (
+        format!(
+            "model: {}\npairs: {} ({} failed)\naccuracy: {:.4} at {:.4}\nbest accuracy: {:.4} at {:.4}\neer: {:.4} at {:.4}\n{tar_at_far}\nthroughput: {:.1} images/s ({} images failed)",
+            report.model,
+            report.pairs,
+            report.failed_pairs.len(),
+            report.accuracy,
+            report.threshold,
+            report.best_accuracy.0,
+            report.best_accuracy.1,
+            report.eer.rate,
+            report.eer.threshold,
+            report.images_per_second(),
+            report.failed_images
+        ),
+        json!({
+            "model": report.model.to_string(),
+            "pairs": report.pairs,
+            "failed_pairs": report
+                .failed_pairs
+                .iter()
+                .map(|failed| json!({ "first": failed.pair.first, "second": failed.pair.second, "reason": failed.reason }))
+                .collect::<Vec<_>>(),
+            "accuracy": report.accuracy,
+            "threshold": report.threshold,
+            "best_accuracy": { "accuracy": report.best_accuracy.0, "threshold": report.best_accuracy.1 },
+            "eer": { "rate": report.eer.rate, "threshold": report.eer.threshold },
+            "tar_at_far": report
+                .tar_at_far
+                .iter()
+                .map(|tar| json!({ "far": tar.target_far, "tar": tar.point.tar(), "threshold": tar.point.threshold }))
+                .collect::<Vec<_>>(),
+            "elapsed_seconds": report.elapsed.as_secs_f64(),
+            "images": report.images,
+            "failed_images": report.failed_images,
+            "images_per_second": report.images_per_second(),
+            "pairs_per_second": report.pairs_per_second(),
+        }),
+    )
+}
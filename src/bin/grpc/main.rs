@@ -0,0 +1,72 @@
+//! gRPC verification service over a shared baklava handler. The schema of the service is `proto/baklava.proto`.
+use baklava::grpc::BaklavaService;
+use baklava::{read_identities, InsightFace};
+use clap::Parser;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tonic::transport::Server;
+
+#[derive(Parser)]
+#[command(
+    name = "baklava-grpc",
+    version,
+    about = "gRPC face verification service"
+)]
+struct Cli {
+    /// Path of the model pack
+    #[arg(long, default_value = "./Megatron")]
+    model: String,
+    /// Address the server listens on
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    address: SocketAddr,
+    /// Number of sessions, which is the number of unary calls processed at the same time
+    #[arg(long, default_value_t = 4)]
+    pool_size: usize,
+    /// Maximum size of a message sent by the clients in bytes
+    #[arg(long, default_value_t = 16 * 1024 * 1024)]
+    max_message_bytes: usize,
+    /// Maximum number of sources images of `Verify`
+    #[arg(long, default_value_t = 16)]
+    max_images: usize,
+    /// Maximum number of identities returned by `Identify`
+    #[arg(long, default_value_t = 100)]
+    max_top_k: usize,
+    /// Maximum number of video streams tracked at the same time by `TrackVideo`
+    #[arg(long, default_value_t = 8)]
+    max_streams: usize,
+    /// Directory with one folder of images per identity, enrolled at startup for `Identify`
+    #[arg(long)]
+    gallery: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let handler = InsightFace::with_session_pool(&cli.model, None, cli.pool_size)?;
+
+    let identities = match &cli.gallery {
+        Some(dir) => read_identities(dir)?,
+        None => vec![],
+    };
+    let (gallery, failures) = handler.enroll_identities(&identities)?;
+    for failure in failures {
+        eprintln!("skipping {failure}");
+    }
+    println!("{} identities enrolled", gallery.len());
+
+    let service = BaklavaService::new(handler, gallery)
+        .with_max_images(cli.max_images)
+        .with_max_top_k(cli.max_top_k)
+        .with_max_streams(cli.max_streams)
+        .into_server()
+        .max_decoding_message_size(cli.max_message_bytes);
+
+    println!("listening on {}", cli.address);
+    Server::builder()
+        .add_service(service)
+        .serve(cli.address)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,387 @@
+//! HTTP verification service over a shared baklava handler.
+//!
+//! Images are sent within the JSON bodies as base64 encoded files (PNG, JPEG...) and decoded with
+//! `FaceImage::decode`. The requests are dispatched to the
+//! blocking pool of tokio, at most one per session of the pool, and the others wait for a session to be released.
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use baklava::error::FFIError;
+use baklava::{
+    read_identities, AsyncInsightFace, FaceEmbedding, FaceImage, IdentityIndex, InsightFace,
+    Methodology,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(
+    name = "baklava-server",
+    version,
+    about = "HTTP face verification service"
+)]
+struct Cli {
+    /// Path of the model pack
+    #[arg(long, default_value = "./Megatron")]
+    model: String,
+    /// Address the server listens on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+    /// Number of sessions, which is the number of requests processed at the same time
+    #[arg(long, default_value_t = 4)]
+    pool_size: usize,
+    /// Maximum size of a request body in bytes
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    max_body_bytes: usize,
+    /// Maximum number of sources images of a verification
+    #[arg(long, default_value_t = 16)]
+    max_images: usize,
+    /// Maximum number of identities returned by `/identify`
+    #[arg(long, default_value_t = 100)]
+    max_top_k: usize,
+    /// Directory with one folder of images per identity, enrolled at startup for `/identify`
+    #[arg(long)]
+    gallery: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+struct AppState {
+    // Dispatches the requests to the blocking pool, at most one per session
+    handler: AsyncInsightFace,
+    gallery: Arc<IdentityIndex<String>>,
+    max_images: usize,
+    max_top_k: usize,
+}
+
+/// ApiError is returned as a JSON body `{ "error": message }` with its status code
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new<S: ToString>(status: StatusCode, message: S) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<FFIError<'_>> for ApiError {
+    fn from(err: FFIError<'_>) -> Self {
+        // Only the errors caused by the images sent by the client are reported as such
+        match err {
+            FFIError::MissingImage
+            | FFIError::Bitmap(_)
+            | FFIError::Stream(_)
+            | FFIError::FaceTrack(_)
+            | FFIError::Feature
+            | FFIError::Model(_) => Self::new(StatusCode::UNPROCESSABLE_ENTITY, err),
+            _ => Self::new(StatusCode::INTERNAL_SERVER_ERROR, err),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Method {
+    #[default]
+    Mean,
+    Median,
+}
+
+impl From<Method> for Methodology {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Mean => Methodology::Mean,
+            Method::Median => Methodology::Median,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    sources: Vec<String>,
+    target: String,
+    #[serde(default)]
+    methodology: Method,
+    /// Cosine above which the faces are similar. Default to the one recommended by the model pack.
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct VerifyResponse {
+    cosine: f32,
+    percentage: f64,
+    similar: bool,
+}
+
+#[derive(Deserialize)]
+struct EmbedRequest {
+    image: String,
+}
+
+#[derive(Serialize)]
+struct EmbedResponse {
+    model: Option<String>,
+    dimension: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct IdentifyRequest {
+    image: String,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct IdentifyMatch {
+    identity: String,
+    cosine: f32,
+    percentage: f64,
+    similar: bool,
+}
+
+#[derive(Serialize)]
+struct IdentifyResponse {
+    matches: Vec<IdentifyMatch>,
+}
+
+fn default_top_k() -> usize {
+    1
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let handler = InsightFace::with_session_pool(&cli.model, None, cli.pool_size)?;
+
+    let identities = match &cli.gallery {
+        Some(dir) => read_identities(dir)?,
+        None => vec![],
+    };
+    let (gallery, failures) = handler.enroll_identities(&identities)?;
+    for failure in failures {
+        eprintln!("skipping {failure}");
+    }
+    println!("{} identities enrolled", gallery.len());
+
+    let pool_size = handler.pool_size();
+    let state = AppState {
+        handler: AsyncInsightFace::new(handler, pool_size),
+        gallery: Arc::new(gallery),
+        max_images: cli.max_images,
+        max_top_k: cli.max_top_k,
+    };
+
+    let app = Router::new()
+        .route("/verify", post(verify))
+        .route("/embed", post(embed))
+        .route("/identify", post(identify))
+        .route("/health", get(health))
+        .route("/model", get(model))
+        .layer(DefaultBodyLimit::max(cli.max_body_bytes))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&cli.address).await?;
+    println!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Decode a base64 encoded image of a request
+///
+/// # Arguments
+///
+/// * `encoded` - &str
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, ApiError> {
+    STANDARD
+        .decode(encoded)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "images need to be base64 encoded"))
+}
+
+fn extract(handler: &InsightFace, encoded: &str) -> Result<FaceEmbedding, ApiError> {
+    let image = FaceImage::decode(&decode_base64(encoded)?)?;
+
+    Ok(handler.extract_image(&image)?)
+}
+
+async fn verify(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    if request.sources.is_empty() || request.sources.len() > state.max_images {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("between 1 and {} sources are expected", state.max_images),
+        ));
+    }
+
+    state
+        .handler
+        .run(move |handler| {
+            let sources = request
+                .sources
+                .iter()
+                .map(|source| extract(handler, source))
+                .collect::<Result<Vec<_>, _>>()?;
+            let target = extract(handler, &request.target)?;
+
+            let (cosine, percentage) =
+                handler.compare(&sources, &target, request.methodology.into())?;
+
+            Ok(VerifyResponse {
+                cosine,
+                percentage,
+                similar: handler.is_similar(cosine, request.threshold),
+            })
+        })
+        .await
+        .map(Json)
+}
+
+async fn embed(
+    State(state): State<AppState>,
+    Json(request): Json<EmbedRequest>,
+) -> Result<Json<EmbedResponse>, ApiError> {
+    state
+        .handler
+        .run(move |handler| {
+            let embedding = extract(handler, &request.image)?;
+
+            Ok(EmbedResponse {
+                model: embedding.model().map(|model| model.pack.clone()),
+                dimension: embedding.len(),
+                embedding: embedding.as_slice().to_vec(),
+            })
+        })
+        .await
+        .map(Json)
+}
+
+async fn identify(
+    State(state): State<AppState>,
+    Json(request): Json<IdentifyRequest>,
+) -> Result<Json<IdentifyResponse>, ApiError> {
+    if state.gallery.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "no identity has been enrolled",
+        ));
+    }
+
+    if request.top_k == 0 || request.top_k > state.max_top_k {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("top_k needs to be between 1 and {}", state.max_top_k),
+        ));
+    }
+
+    let gallery = state.gallery.clone();
+    state
+        .handler
+        .run(move |handler| {
+            let probe = extract(handler, &request.image)?;
+
+            let matches = gallery
+                .search(&probe, request.top_k)?
+                .into_iter()
+                .map(|candidate| IdentifyMatch {
+                    identity: candidate.key,
+                    cosine: candidate.cosine,
+                    percentage: candidate.percentage,
+                    similar: handler.is_similar(candidate.cosine, request.threshold),
+                })
+                .collect();
+
+            Ok(IdentifyResponse { matches })
+        })
+        .await
+        .map(Json)
+}
+
+async fn health(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "available_sessions": state.handler.available_slots(),
+        "identities": state.gallery.len(),
+    }))
+}
+
+async fn model(State(state): State<AppState>) -> Json<Value> {
+    let handler = state.handler.handler();
+    let info = handler.model_info();
+    let converter = handler.similarity_converter();
+
+    Json(json!({
+        "pack": info.pack,
+        "version": info.version.to_string(),
+        "feature_length": info.feature_length,
+        "information": info.information,
+        "recommended_threshold": handler.runtime().recommended_threshold(),
+        "similarity_converter": {
+            "threshold": converter.threshold,
+            "middle_score": converter.middle_score,
+            "steepness": converter.steepness,
+            "output_min": converter.output_min,
+            "output_max": converter.output_max,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_base64, ApiError};
+    use axum::http::StatusCode;
+    use baklava::error::FFIError;
+
+    #[test]
+    fn expect_uploaded_image_to_be_base64_decoded() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+
+        let err = decode_base64("not base64!").unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn expect_only_image_errors_to_be_client_errors() {
+        let status = |err: FFIError<'static>| ApiError::from(err).status;
+
+        assert_eq!(
+            status(FFIError::FaceTrack("no face")),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(FFIError::MissingImage),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(FFIError::IO("temp file")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(status(FFIError::Session), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            status(FFIError::Runtime("poisoned")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::similarity::SimilarityConverter;
+use crate::{InsightFace, Methodology};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// LabeledPair is a pair of images which either show the same person (genuine) or two different persons (impostor)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabeledPair {
+    pub first: String,
+    pub second: String,
+    pub genuine: bool,
+}
+
+impl LabeledPair {
+    /// Read the pairs of a CSV file where each line is `first,second,label`. The label is either `1`, `true` or
+    /// `genuine` for a genuine pair and `0`, `false` or `impostor` for an impostor pair. Empty lines, lines starting
+    /// with `#` and a `first,second,label` header are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - P
+    pub fn read_csv<'a, P: AsRef<Path>>(path: P) -> Result<Vec<Self>, FFIError<'a>> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| FFIError::Calibration("unable to read the pairs file"))?;
+
+        Self::parse_csv(&content)
+    }
+
+    /// Parse the pairs of a CSV content. See `read_csv` for the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - &str
+    pub fn parse_csv<'a>(content: &str) -> Result<Vec<Self>, FFIError<'a>> {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter(|line| !line.eq_ignore_ascii_case("first,second,label"))
+            .map(|line| {
+                let columns = line.split(',').map(str::trim).collect::<Vec<_>>();
+                let [first, second, label] = columns[..] else {
+                    return Err(FFIError::Calibration(
+                        "each line needs to be first,second,label",
+                    ));
+                };
+
+                let genuine = match label.to_ascii_lowercase().as_str() {
+                    "1" | "true" | "genuine" => true,
+                    "0" | "false" | "impostor" => false,
+                    _ => return Err(FFIError::Calibration("unknown label")),
+                };
+
+                Ok(Self {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                    genuine,
+                })
+            })
+            .collect()
+    }
+}
+
+/// OperatingPoint is the outcome of using a cosine threshold to decide whether two faces are the same person
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperatingPoint {
+    /// Pairs whose cosine is equal or above the threshold are accepted
+    pub threshold: f32,
+    /// False accept rate, the share of impostor pairs which are accepted
+    pub far: f64,
+    /// False reject rate, the share of genuine pairs which are rejected
+    pub frr: f64,
+}
+
+impl OperatingPoint {
+    /// Return the true accept rate, the share of genuine pairs which are accepted
+    pub fn tar(&self) -> f64 {
+        1. - self.frr
+    }
+}
+
+/// EqualErrorRate is the operating point where the false accept rate and the false reject rate are equal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqualErrorRate {
+    pub threshold: f32,
+    /// Mean of the false accept rate and the false reject rate at the threshold
+    pub rate: f64,
+}
+
+/// Calibration holds the cosines of labeled pairs. It computes the error rates of any threshold, the ROC and DET
+/// curves, and the threshold needed to reach a target false accept rate on the population the pairs come from.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    // Sorted by descending cosine
+    scores: Vec<(f32, bool)>,
+    genuines: usize,
+    impostors: usize,
+}
+
+impl Calibration {
+    /// Create a calibration out of the cosines of labeled pairs. At least one genuine and one impostor pair are
+    /// required.
+    ///
+    /// # Arguments
+    ///
+    /// * `scores` - `Vec<(f32, bool)>` the cosine of each pair and whether it is genuine
+    pub fn from_scores<'a>(mut scores: Vec<(f32, bool)>) -> Result<Self, FFIError<'a>> {
+        if scores.iter().any(|(cosine, _)| !cosine.is_finite()) {
+            return Err(FFIError::Calibration("a cosine is not a finite number"));
+        }
+
+        let genuines = scores.iter().filter(|(_, genuine)| *genuine).count();
+        let impostors = scores.len() - genuines;
+        if genuines == 0 || impostors == 0 {
+            return Err(FFIError::Calibration(
+                "at least one genuine and one impostor pair are required",
+            ));
+        }
+
+        scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(Self {
+            scores,
+            genuines,
+            impostors,
+        })
+    }
+
+    /// Return the cosines of the pairs sorted by descending cosine along with whether the pair is genuine
+    pub fn scores(&self) -> &[(f32, bool)] {
+        &self.scores
+    }
+
+    /// Return the error rates of a threshold
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - f32
+    pub fn rates(&self, threshold: f32) -> OperatingPoint {
+        let (accepted_genuines, accepted_impostors) = self
+            .scores
+            .iter()
+            .take_while(|(cosine, _)| *cosine >= threshold)
+            .fold(
+                (0, 0),
+                |(genuines, impostors), (_, genuine)| match genuine {
+                    true => (genuines + 1, impostors),
+                    false => (genuines, impostors + 1),
+                },
+            );
+
+        self.point(threshold, accepted_genuines, accepted_impostors)
+    }
+
+    /// Return the operating points of every distinct cosine sorted by descending threshold. The first point rejects
+    /// every pair. Plotting the true accept rate against the false accept rate gives the ROC curve, plotting the false
+    /// reject rate against the false accept rate gives the DET curve.
+    pub fn curve(&self) -> Vec<OperatingPoint> {
+        let mut points = vec![self.point(self.scores[0].0.next_up(), 0, 0)];
+        let (mut accepted_genuines, mut accepted_impostors) = (0, 0);
+
+        for (idx, (cosine, genuine)) in self.scores.iter().enumerate() {
+            match genuine {
+                true => accepted_genuines += 1,
+                false => accepted_impostors += 1,
+            }
+
+            // Pairs with the same cosine are accepted together
+            if self
+                .scores
+                .get(idx + 1)
+                .is_none_or(|(next, _)| next < cosine)
+            {
+                points.push(self.point(*cosine, accepted_genuines, accepted_impostors));
+            }
+        }
+
+        points
+    }
+
+    /// Return the share of pairs which are correctly classified by a threshold
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - f32
+    pub fn accuracy(&self, threshold: f32) -> f64 {
+        self.point_accuracy(&self.rates(threshold))
+    }
+
+    /// Return the highest accuracy reachable with a single threshold along with the highest threshold reaching it
+    pub fn best_accuracy(&self) -> (f64, f32) {
+        self.curve()
+            .iter()
+            .map(|point| (self.point_accuracy(point), point.threshold))
+            // The strictest threshold is kept on a tie
+            .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .unwrap_or((0., 0.))
+    }
+
+    /// Return the equal error rate
+    pub fn eer(&self) -> EqualErrorRate {
+        let point = self
+            .curve()
+            .into_iter()
+            .min_by(|a, b| (a.far - a.frr).abs().total_cmp(&(b.far - b.frr).abs()))
+            .unwrap_or_else(|| self.rates(0.));
+
+        EqualErrorRate {
+            threshold: point.threshold,
+            rate: (point.far + point.frr) / 2.,
+        }
+    }
+
+    /// Return the operating point with the lowest threshold whose false accept rate does not exceed the target
+    ///
+    /// # Arguments
+    ///
+    /// * `target_far` - f64 e.g. 0.001 for one false accept every thousand impostor pairs
+    pub fn threshold_for_far(&self, target_far: f64) -> OperatingPoint {
+        let curve = self.curve();
+
+        // The false accept rate only grows as the threshold decreases, the first point accepts nothing
+        curve
+            .iter()
+            .take_while(|point| point.far <= target_far)
+            .last()
+            .copied()
+            .unwrap_or(curve[0])
+    }
+
+    /// Return a converter whose threshold reaches the target false accept rate. The cosine of the threshold is
+    /// converted to the middle score of the base converter.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_far` - f64
+    /// * `base` - SimilarityConverter
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::calibration::LabeledPair;
+    /// use baklava::InsightFace;
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let pairs = LabeledPair::read_csv("./pairs.csv").unwrap();
+    ///
+    /// let calibration = handler.calibrate(&pairs).unwrap();
+    /// let converter = calibration.converter(0.001, *handler.similarity_converter());
+    /// handler.set_similarity_converter(converter);
+    /// ```
+    pub fn converter(&self, target_far: f64, base: SimilarityConverter) -> SimilarityConverter {
+        SimilarityConverter {
+            threshold: self.threshold_for_far(target_far).threshold,
+            ..base
+        }
+    }
+
+    /// Return the share of pairs which are correctly classified at an operating point
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - &OperatingPoint
+    fn point_accuracy(&self, point: &OperatingPoint) -> f64 {
+        let correct_genuines = self.genuines as f64 * point.tar();
+        let correct_impostors = self.impostors as f64 * (1. - point.far);
+
+        (correct_genuines + correct_impostors) / self.scores.len() as f64
+    }
+
+    /// Build the operating point out of the number of accepted pairs
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - f32
+    /// * `accepted_genuines` - usize
+    /// * `accepted_impostors` - usize
+    fn point(
+        &self,
+        threshold: f32,
+        accepted_genuines: usize,
+        accepted_impostors: usize,
+    ) -> OperatingPoint {
+        OperatingPoint {
+            threshold,
+            far: accepted_impostors as f64 / self.impostors as f64,
+            frr: (self.genuines - accepted_genuines) as f64 / self.genuines as f64,
+        }
+    }
+}
+
+impl InsightFace {
+    /// Extract the embeddings of labeled pairs and compute their cosines. Each image is only extracted once even if
+    /// it belongs to several pairs.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - `&[LabeledPair]`
+    pub fn calibrate<'a>(&self, pairs: &[LabeledPair]) -> Result<Calibration, FFIError<'a>> {
+        let mut embeddings: HashMap<&str, FaceEmbedding> = HashMap::new();
+        let mut scores = Vec::with_capacity(pairs.len());
+
+        for pair in pairs {
+            for path in [pair.first.as_str(), pair.second.as_str()] {
+                if !embeddings.contains_key(path) {
+                    embeddings.insert(path, self.extract(path)?);
+                }
+            }
+
+            let (cosine, _) = self.compare(
+                std::slice::from_ref(&embeddings[pair.first.as_str()]),
+                &embeddings[pair.second.as_str()],
+                Methodology::Mean,
+            )?;
+
+            scores.push((cosine, pair.genuine));
+        }
+
+        Calibration::from_scores(scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calibration, LabeledPair};
+    use crate::similarity::SimilarityConverter;
+
+    fn calibration() -> Calibration {
+        Calibration::from_scores(vec![
+            (0.9, true),
+            (0.8, true),
+            (0.7, true),
+            (0.5, false),
+            (0.6, true),
+            (0.4, false),
+            (0.65, false),
+            (0.2, false),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn expect_rates_to_be_computed_at_a_threshold() {
+        let point = calibration().rates(0.6);
+
+        assert_eq!(point.far, 0.25);
+        assert_eq!(point.frr, 0.);
+        assert_eq!(point.tar(), 1.);
+    }
+
+    #[test]
+    fn expect_curve_to_go_from_rejecting_to_accepting_everything() {
+        let curve = calibration().curve();
+
+        assert_eq!(curve.len(), 9);
+        assert_eq!((curve[0].far, curve[0].frr), (0., 1.));
+        assert_eq!((curve[8].far, curve[8].frr), (1., 0.));
+        assert!(curve.windows(2).all(|w| w[0].far <= w[1].far));
+        assert!(curve.windows(2).all(|w| w[0].frr >= w[1].frr));
+    }
+
+    #[test]
+    fn expect_eer_and_threshold_for_far() {
+        let calibration = calibration();
+        assert_eq!(calibration.accuracy(0.6), 0.875);
+        assert_eq!(calibration.best_accuracy(), (0.875, 0.7));
+
+        let eer = calibration.eer();
+        assert_eq!(eer.threshold, 0.65);
+        assert_eq!(eer.rate, 0.25);
+
+        let point = calibration.threshold_for_far(0.);
+        assert_eq!(point.threshold, 0.7);
+        assert_eq!(point.frr, 0.25);
+
+        let converter = calibration.converter(0.3, SimilarityConverter::default());
+        assert_eq!(converter.threshold, 0.6);
+        assert_eq!(
+            converter.middle_score,
+            SimilarityConverter::default().middle_score
+        );
+    }
+
+    #[test]
+    fn expect_pairs_to_be_parsed() {
+        let pairs = LabeledPair::parse_csv(
+            "first,second,label\n# comment\n\na.png,b.png,1\nc.png, d.png ,impostor\n",
+        )
+        .unwrap();
+
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs[0].genuine);
+        assert_eq!(pairs[1].second, "d.png");
+        assert!(!pairs[1].genuine);
+
+        assert!(LabeledPair::parse_csv("a.png,b.png").is_err());
+        assert!(LabeledPair::parse_csv("a.png,b.png,maybe").is_err());
+        assert!(Calibration::from_scores(vec![(0.5, true)]).is_err());
+    }
+}
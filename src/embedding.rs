@@ -0,0 +1,223 @@
+use crate::error::FFIError;
+use crate::face;
+use crate::ffi_wrapper::{HFFaceFeatureExtractCpy, HFGetFeatureLength};
+use crate::handle::Bitmap;
+use crate::image::FaceImage;
+use crate::model::ModelInfo;
+use crate::{InsightFace, SUCCESS};
+use autocxx::prelude::*;
+use std::ffi::CString;
+use std::sync::Arc;
+
+/// FaceEmbedding is an owned copy of the feature vector extracted by InspireFace for a single face.
+/// Unlike the features used internally by the InsightFace handler, it can be stored, cloned and sent
+/// across threads freely.
+///
+/// The embeddings extracted by a handler are tagged with the model pack which extracted them. Tagged embeddings
+/// coming from different model packs are never compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceEmbedding {
+    data: Vec<f32>,
+    model: Option<Arc<ModelInfo>>,
+}
+
+impl FaceEmbedding {
+    /// Create a new embedding from a feature vector previously extracted by InspireFace. The embedding is not
+    /// tagged with a model pack, hence only its dimension is checked before being compared.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - `Vec<f32>`
+    pub fn new(data: Vec<f32>) -> Self {
+        Self { data, model: None }
+    }
+
+    /// Create a new embedding extracted by the given model pack
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - `Vec<f32>`
+    /// * `model` - `Arc<ModelInfo>`
+    pub fn with_model(data: Vec<f32>, model: Arc<ModelInfo>) -> Self {
+        Self {
+            data,
+            model: Some(model),
+        }
+    }
+
+    /// Create a new embedding tagged with the same model pack as this one
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - `Vec<f32>`
+    pub(crate) fn with_same_model(&self, data: Vec<f32>) -> Self {
+        Self {
+            data,
+            model: self.model.clone(),
+        }
+    }
+
+    /// Return the model pack which extracted the embedding if it is known
+    pub fn model(&self) -> Option<&ModelInfo> {
+        self.model.as_deref()
+    }
+
+    /// Return whether the embedding can be compared with another one. Both of them need to have the same dimension
+    /// and, when they are tagged, to be extracted by the same model pack.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - &FaceEmbedding
+    pub fn is_compatible(&self, other: &FaceEmbedding) -> bool {
+        self.len() == other.len()
+            && match (self.model(), other.model()) {
+                (Some(model), Some(other)) => model == other,
+                _ => true,
+            }
+    }
+
+    /// Check that the embedding can be used with the given model pack
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - &ModelInfo
+    pub(crate) fn check_model<'a>(&self, model: &ModelInfo) -> Result<(), FFIError<'a>> {
+        if self.model().is_some_and(|tag| tag != model) {
+            return Err(FFIError::Model(
+                "the embedding has been extracted by another model pack",
+            ));
+        }
+
+        if self.len() != model.feature_length {
+            return Err(FFIError::Model(
+                "the embedding does not have the feature length of the model",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Return the feature vector
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Return the dimension of the feature vector
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Return whether the feature vector is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Consume the embedding and return the feature vector
+    pub fn into_vec(self) -> Vec<f32> {
+        self.data
+    }
+}
+
+impl From<Vec<f32>> for FaceEmbedding {
+    fn from(data: Vec<f32>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl InsightFace {
+    /// Extract the embedding of the face of an image. The face is selected the same way as `prepare_images` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    pub fn extract_embedding<'a, S: AsRef<str>>(
+        &mut self,
+        img_path: S,
+    ) -> Result<FaceEmbedding, FFIError<'a>> {
+        self.extract(img_path)
+    }
+
+    /// Extract the embedding of the face of an image with a session of the pool. It does not mutate the handler,
+    /// hence a shared handler can extract up to `pool_size` embeddings concurrently.
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::InsightFace;
+    /// use std::sync::Arc;
+    ///
+    /// let handler = Arc::new(InsightFace::with_session_pool("./Megatron", None, 2).unwrap());
+    ///
+    /// let worker = handler.clone();
+    /// let thread = std::thread::spawn(move || worker.extract("./face1_test.png"));
+    /// let embedding = handler.extract("./face2_test.png").unwrap();
+    /// ```
+    pub fn extract<'a, S: AsRef<str>>(&self, img_path: S) -> Result<FaceEmbedding, FFIError<'a>> {
+        let img_path = CString::new(img_path.as_ref()).map_err(|_| FFIError::MissingImage)?;
+
+        self.extract_bitmap(&Bitmap::from_path(&img_path)?)
+    }
+
+    /// Extract the embedding of the face of an in-memory image (e.g. a decoded video frame)
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{FaceImage, InsightFace};
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let frame = FaceImage::new(640, 480, 3, vec![0; 640 * 480 * 3]).unwrap();
+    /// let embedding = handler.extract_image(&frame);
+    /// ```
+    pub fn extract_image<'a>(&self, image: &FaceImage) -> Result<FaceEmbedding, FFIError<'a>> {
+        self.extract_bitmap(&image.to_bitmap()?)
+    }
+
+    fn extract_bitmap<'a>(&self, bitmap: &Bitmap) -> Result<FaceEmbedding, FFIError<'a>> {
+        let model = self.model_info();
+        let mut data = vec![0.; model.feature_length];
+        let session = self.pool.acquire()?;
+
+        face::execute_face_track_on_bitmap(
+            session.session,
+            bitmap,
+            |session, stream_ptr, face_data| {
+                let token = face::read_tokens(face_data)?
+                    .first()
+                    .ok_or(FFIError::FaceTrack("Unable to get the processed feature"))?;
+
+                unsafe {
+                    if HFFaceFeatureExtractCpy(session, stream_ptr, token, data.as_mut_ptr()).0
+                        != SUCCESS
+                    {
+                        return Err(FFIError::FaceTrack(
+                            "Unable to extract feature from stream_ptr",
+                        ));
+                    }
+                }
+
+                Ok(())
+            },
+        )?;
+
+        Ok(FaceEmbedding::with_model(data, model))
+    }
+}
+
+/// Return the length of the features extracted by the loaded model
+pub(crate) fn feature_length<'a>() -> Result<usize, FFIError<'a>> {
+    let mut length = c_int(0);
+    unsafe {
+        if HFGetFeatureLength(&mut length).0 != SUCCESS {
+            return Err(FFIError::Feature);
+        }
+    }
+
+    Ok(length.0 as usize)
+}
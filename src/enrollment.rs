@@ -0,0 +1,211 @@
+use crate::error::FFIError;
+use crate::index::IdentityIndex;
+use crate::template::FaceTemplate;
+use crate::InsightFace;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+// Extensions of the images picked up when reading an identity folder
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Identity is a folder holding the images of one person, the name of the folder being the name of the person
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Identity {
+    pub name: String,
+    pub path: PathBuf,
+    /// Images of the folder, sorted by name
+    pub images: Vec<PathBuf>,
+}
+
+/// Enrollment is the outcome of the enrollment of the images of an identity
+#[derive(Debug)]
+pub struct Enrollment<'a> {
+    /// Template fused from the images where a face could be extracted
+    pub template: Result<FaceTemplate, FFIError<'a>>,
+    /// Images where a face could not be extracted along with the error
+    pub skipped: Vec<(PathBuf, FFIError<'a>)>,
+}
+
+/// EnrollmentFailure is an image, or a whole identity, which has been left out of an index
+#[derive(Debug)]
+pub struct EnrollmentFailure<'a> {
+    pub identity: String,
+    /// Image which failed, or the folder of the identity when no template could be built
+    pub path: PathBuf,
+    pub error: FFIError<'a>,
+}
+
+impl fmt::Display for EnrollmentFailure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.path.display(),
+            self.identity,
+            self.error
+        )
+    }
+}
+
+/// Read the identities of a directory with one folder of images per identity. Files which are not images (png, jpg,
+/// jpeg, bmp) are ignored. The identities are sorted by name.
+///
+/// # Arguments
+///
+/// * `dir` - P
+pub fn read_identities<'a, P: AsRef<Path>>(dir: P) -> Result<Vec<Identity>, FFIError<'a>> {
+    let read_dir = |dir: &Path| {
+        fs::read_dir(dir)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|_| FFIError::IO("unable to read the identities directory"))
+    };
+
+    let mut identities = vec![];
+    for path in read_dir(dir.as_ref())? {
+        if !path.is_dir() {
+            continue;
+        }
+
+        let mut images = read_dir(&path)?;
+        images.retain(|image| is_image(image));
+        images.sort();
+
+        identities.push(Identity {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path,
+            images,
+        });
+    }
+
+    identities.sort();
+
+    Ok(identities)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|supported| ext.eq_ignore_ascii_case(supported))
+        })
+}
+
+impl InsightFace {
+    /// Build the template of an identity out of its images. Images where no face can be extracted are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `images` - `&[PathBuf]`
+    pub fn enroll_identity<'a>(&self, images: &[PathBuf]) -> Enrollment<'a> {
+        let mut embeddings = vec![];
+        let mut skipped = vec![];
+        for image in images {
+            match self.extract(image.to_string_lossy()) {
+                Ok(embedding) => embeddings.push(embedding),
+                Err(err) => skipped.push((image.clone(), err)),
+            }
+        }
+
+        Enrollment {
+            template: FaceTemplate::new(&embeddings),
+            skipped,
+        }
+    }
+
+    /// Enroll a template per identity into an index searched exhaustively, whose percentages are computed with the
+    /// converter of the handler. The images and identities which could not be enrolled are returned along with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `identities` - `&[Identity]`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::enrollment::read_identities;
+    /// use baklava::InsightFace;
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let identities = read_identities("./identities").unwrap();
+    ///
+    /// let (index, failures) = handler.enroll_identities(&identities).unwrap();
+    /// let probe = handler.extract("./face1_test.png").unwrap();
+    /// let matches = index.search(&probe, 3).unwrap();
+    /// ```
+    pub fn enroll_identities<'a>(
+        &self,
+        identities: &[Identity],
+    ) -> Result<(IdentityIndex<String>, Vec<EnrollmentFailure<'a>>), FFIError<'a>> {
+        let mut index = IdentityIndex::new().with_converter(*self.similarity_converter());
+        let mut failures = vec![];
+
+        for identity in identities {
+            let enrollment = self.enroll_identity(&identity.images);
+            failures.extend(enrollment.skipped.into_iter().map(|(path, error)| {
+                EnrollmentFailure {
+                    identity: identity.name.clone(),
+                    path,
+                    error,
+                }
+            }));
+
+            match enrollment.template {
+                Ok(template) => index.insert(identity.name.clone(), template.embedding())?,
+                Err(error) => failures.push(EnrollmentFailure {
+                    identity: identity.name.clone(),
+                    path: identity.path.clone(),
+                    error,
+                }),
+            }
+        }
+
+        Ok((index, failures))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_identities;
+    use std::fs;
+
+    #[test]
+    fn expect_identity_folders_to_be_read_sorted() {
+        let root = std::env::temp_dir().join(format!("baklava-identities-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (identity, file) in [
+            ("bob", "2.png"),
+            ("bob", "1.JPG"),
+            ("bob", "notes.txt"),
+            ("alice", "1.jpeg"),
+        ] {
+            fs::create_dir_all(root.join(identity)).unwrap();
+            fs::write(root.join(identity).join(file), b"").unwrap();
+        }
+        fs::write(root.join("readme.md"), b"").unwrap();
+
+        let identities = read_identities(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let names = identities
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["alice", "bob"]);
+        assert_eq!(
+            identities[1].images,
+            [
+                root.join("bob").join("1.JPG"),
+                root.join("bob").join("2.png")
+            ]
+        );
+        assert!(read_identities(root.join("missing")).is_err());
+    }
+}
@@ -13,6 +13,15 @@ pub enum FFIError<'a> {
     Comparison(&'a str),
     SamplingSize,
     IO(&'a str),
+    Alignment(&'a str),
+    Gallery(&'a str),
+    Index(&'a str),
+    Template(&'a str),
+    Async(&'a str),
+    Runtime(&'a str),
+    Model(&'a str),
+    Calibration(&'a str),
+    Benchmark(&'a str),
 }
 
 impl<'a> Error for FFIError<'a> {}
@@ -29,7 +38,16 @@ impl<'a> fmt::Display for FFIError<'a> {
             Self::FaceTrack(msg) => write!(f, "Facetrack encountered an error due to: {msg}"),
             Self::Comparison(msg) => write!(f, "Unable to compare image due to: {msg}"),
             Self::SamplingSize => write!(f, "Unable to prepare image set due to sampling size value being different than what's expected"),
-            Self::IO(msg) => write!(f, "Unable to preprare the images due to: {msg}")
+            Self::IO(msg) => write!(f, "Unable to preprare the images due to: {msg}"),
+            Self::Alignment(msg) => write!(f, "Unable to align the face due to: {msg}"),
+            Self::Gallery(msg) => write!(f, "Unable to use the gallery due to: {msg}"),
+            Self::Index(msg) => write!(f, "Unable to use the index due to: {msg}"),
+            Self::Template(msg) => write!(f, "Unable to build the template due to: {msg}"),
+            Self::Async(msg) => write!(f, "Unable to run the asynchronous call due to: {msg}"),
+            Self::Runtime(msg) => write!(f, "Unable to manage the runtime due to: {msg}"),
+            Self::Model(msg) => write!(f, "Unable to use the model due to: {msg}"),
+            Self::Calibration(msg) => write!(f, "Unable to calibrate the threshold due to: {msg}"),
+            Self::Benchmark(msg) => write!(f, "Unable to run the benchmark due to: {msg}")
         }
     }
 }
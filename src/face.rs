@@ -0,0 +1,372 @@
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    baklava_face_data_det_confidence, baklava_face_data_detected_num, baklava_face_data_pitch,
+    baklava_face_data_rect, baklava_face_data_roll, baklava_face_data_track_id,
+    baklava_face_data_yaw, HFDetectMode, HFExecuteFaceTrack, HFFaceBasicToken,
+    HFGetFaceFiveKeyPointsFromFaceToken, HFGetTokens, HFMultipleFaceData, HFaceRect, HPoint2f,
+    HF_ENABLE_FACE_POSE,
+};
+use crate::handle::{Bitmap, ImageStream, Session};
+use crate::image::FaceImage;
+use crate::runtime::Runtime;
+use crate::{InsightFace, SUCCESS};
+use autocxx::c_void;
+use autocxx::prelude::*;
+use std::sync::MutexGuard;
+use std::{ffi::CString, mem};
+
+/// FaceRect is the bounding box of a detected face in pixel coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaceRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// FacePose holds the euler angles of a detected face in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FacePose {
+    pub roll: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// FacePoint is a landmark of a face in pixel coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FacePoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// DetectedFace is a face which has been found by the face tracker of a session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedFace {
+    /// Id assigned by the tracker. It stays the same across frames when the session is in a tracking mode
+    pub track_id: i32,
+    pub rect: FaceRect,
+    /// Detection confidence ranging from 0 to 1
+    pub confidence: f32,
+    pub pose: FacePose,
+    /// Five key points of the face: left eye, right eye, nose, left & right corners of the mouth
+    pub landmarks: [FacePoint; 5],
+}
+
+/// DetectionSession is an InspireFace session detecting up to `max_faces` faces on each image. The sessions of the
+/// handler only detect the face used for the comparison, a detection session returns every face of the image.
+pub struct DetectionSession {
+    session: Session,
+    max_faces: i32,
+    // Declared last so that the SDK outlives the session
+    _runtime: Runtime,
+}
+
+impl DetectionSession {
+    /// Create a new detection session. The InsightFace handler is required as the model needs to be launched
+    /// beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &InsightFace
+    /// * `max_faces` - i32
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{DetectionSession, InsightFace};
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let mut session = DetectionSession::new(&handler, 20).unwrap();
+    /// let faces = session.detect("./face1_test.png").unwrap();
+    /// ```
+    pub fn new<'a>(handler: &InsightFace, max_faces: i32) -> Result<Self, FFIError<'a>> {
+        let session = Session::new(
+            HF_ENABLE_FACE_POSE,
+            HFDetectMode::HF_DETECT_MODE_ALWAYS_DETECT,
+            max_faces,
+        )?;
+
+        Ok(Self {
+            session,
+            max_faces,
+            _runtime: handler.runtime().clone(),
+        })
+    }
+
+    /// Return the maximum number of faces detected on an image
+    pub fn max_faces(&self) -> i32 {
+        self.max_faces
+    }
+
+    /// Detect the faces of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    pub fn detect<'a, S: AsRef<str>>(
+        &mut self,
+        img_path: S,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        execute_face_track(self.session.as_ptr(), img_path.as_ref(), |_, _, data| {
+            read_faces(data)
+        })
+    }
+
+    /// Detect the faces of an in-memory image
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    pub fn detect_image<'a>(
+        &mut self,
+        image: &FaceImage,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        execute_face_track_on_bitmap(self.session.as_ptr(), &image.to_bitmap()?, |_, _, data| {
+            read_faces(data)
+        })
+    }
+}
+
+impl FaceRect {
+    /// Return the area of the bounding box in pixels
+    pub fn area(&self) -> i64 {
+        self.width as i64 * self.height as i64
+    }
+}
+
+impl From<HFaceRect> for FaceRect {
+    fn from(rect: HFaceRect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+impl FacePose {
+    /// Return a score ranging from 0 to 1 which tells how much the face is looking at the camera.
+    /// The roll is ignored as it's corrected by the face alignment done before the feature extraction.
+    pub fn frontal_score(&self) -> f32 {
+        let yaw = (1. - self.yaw.abs() / 90.).max(0.);
+        let pitch = (1. - self.pitch.abs() / 90.).max(0.);
+
+        yaw * pitch
+    }
+}
+
+impl InsightFace {
+    /// Detect the faces of an image, at most `max_detected_faces` of them. See `detect_selected_face` for the face
+    /// used by `prepare_images` and `prepare_target_image`.
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - S
+    pub fn detect_faces<'a, S: AsRef<str>>(
+        &mut self,
+        img_path: S,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        self.detect_faces_in_image(&FaceImage::from_path(img_path)?)
+    }
+
+    /// Detect the faces of an in-memory image, at most `max_detected_faces` of them. Like `extract`, it can be called
+    /// from several threads at the same time, at most one call per session of the pool runs at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    pub fn detect_faces_in_image<'a>(
+        &self,
+        image: &FaceImage,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        // The session of the pool bounds the number of detection sessions to the size of the pool
+        let _session = self.pool.acquire()?;
+        let mut detector = match self.idle_detectors()?.pop() {
+            Some(detector) => detector,
+            None => DetectionSession::new(self, self.max_detected_faces)?,
+        };
+
+        let faces = detector.detect_image(image);
+        self.idle_detectors()?.push(detector);
+
+        faces
+    }
+
+    /// Detect the face of an in-memory image which is used by `extract`, `prepare_images` and
+    /// `prepare_target_image` when the image holds several faces. Return None when no face has been found.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - &FaceImage
+    pub fn detect_selected_face<'a>(
+        &self,
+        image: &FaceImage,
+    ) -> Result<Option<DetectedFace>, FFIError<'a>> {
+        let bitmap = image.to_bitmap()?;
+        let session = self.pool.acquire()?;
+
+        execute_face_track_on_bitmap(session.session, &bitmap, |_, _, data| {
+            Ok(read_faces(data)?.into_iter().next())
+        })
+    }
+
+    /// Return the maximum number of faces returned by `detect_faces`
+    pub fn max_detected_faces(&self) -> i32 {
+        self.max_detected_faces
+    }
+
+    /// Set the maximum number of faces returned by `detect_faces`. Default to 20.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_faces` - i32
+    pub fn set_max_detected_faces(&mut self, max_faces: i32) -> &mut Self {
+        self.max_detected_faces = max_faces.max(1);
+        // The idle sessions have been created with the previous maximum
+        if let Ok(detectors) = self.detectors.get_mut() {
+            detectors.clear();
+        }
+
+        self
+    }
+
+    /// Return the detection sessions which are not in use
+    fn idle_detectors<'a>(&self) -> Result<MutexGuard<'_, Vec<DetectionSession>>, FFIError<'a>> {
+        self.detectors
+            .lock()
+            .map_err(|_| FFIError::IO("Unable to acquire the detection sessions lock"))
+    }
+}
+
+/// Read the faces stored in the HFMultipleFaceData filled by HFExecuteFaceTrack. The faces are
+/// returned in the same order as the tokens of the face data.
+///
+/// # Arguments
+///
+/// * `data` - &mut HFMultipleFaceData
+pub(crate) fn read_faces<'a>(
+    data: &mut HFMultipleFaceData,
+) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+    let mut faces = unsafe {
+        let detected = baklava_face_data_detected_num(data).0;
+
+        (0..detected)
+            .map(|idx| DetectedFace {
+                track_id: baklava_face_data_track_id(data, c_int(idx)).0,
+                rect: FaceRect::from(baklava_face_data_rect(data, c_int(idx))),
+                confidence: baklava_face_data_det_confidence(data, c_int(idx)),
+                pose: FacePose {
+                    roll: baklava_face_data_roll(data, c_int(idx)),
+                    yaw: baklava_face_data_yaw(data, c_int(idx)),
+                    pitch: baklava_face_data_pitch(data, c_int(idx)),
+                },
+                landmarks: [FacePoint::default(); 5],
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if faces.is_empty() {
+        return Ok(faces);
+    }
+
+    let tokens = read_tokens(data)?;
+    for (face, token) in faces.iter_mut().zip(tokens.iter()) {
+        let mut points = [HPoint2f { x: 0., y: 0. }; 5];
+
+        unsafe {
+            let res = HFGetFaceFiveKeyPointsFromFaceToken(
+                token,
+                points.as_mut_ptr(),
+                c_int(points.len() as i32),
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::FaceTrack(
+                    "Unable to get the key points of the face",
+                ));
+            }
+        }
+
+        for (landmark, point) in face.landmarks.iter_mut().zip(points.iter()) {
+            *landmark = FacePoint {
+                x: point.x,
+                y: point.y,
+            };
+        }
+    }
+
+    Ok(faces)
+}
+
+/// Return the tokens stored in the HFMultipleFaceData. The tokens are only valid until the next face track of the session.
+///
+/// # Arguments
+///
+/// * `data` - &mut HFMultipleFaceData
+pub(crate) fn read_tokens<'a>(
+    data: &mut HFMultipleFaceData,
+) -> Result<&[HFFaceBasicToken], FFIError<'a>> {
+    unsafe {
+        let tokens_slice = HFGetTokens(data);
+        if tokens_slice.ptr.is_null() {
+            return Err(FFIError::FaceTrack(
+                "Unable to construct list of tokens due to tokens slice being null",
+            ));
+        }
+
+        Ok(std::slice::from_raw_parts(
+            tokens_slice.ptr as *const HFFaceBasicToken,
+            tokens_slice.len as usize,
+        ))
+    }
+}
+
+/// Run the face tracking of a session on an image and hand the result to the callback along with the session and the stream.
+/// The image & stream are released once the callback returns.
+///
+/// # Arguments
+///
+/// * `session` - *mut c_void
+/// * `img_path` - &str
+/// * `callback` - F
+pub(crate) fn execute_face_track<'a, T, F>(
+    session: *mut c_void,
+    img_path: &str,
+    callback: F,
+) -> Result<T, FFIError<'a>>
+where
+    F: FnOnce(*mut c_void, *mut c_void, &mut HFMultipleFaceData) -> Result<T, FFIError<'a>>,
+{
+    let img_path = CString::new(img_path).map_err(|_| FFIError::MissingImage)?;
+    let bitmap = Bitmap::from_path(&img_path)?;
+
+    execute_face_track_on_bitmap(session, &bitmap, callback)
+}
+
+/// Same as `execute_face_track` but run the face tracking on a bitmap which is already loaded
+///
+/// # Arguments
+///
+/// * `session` - *mut c_void
+/// * `bitmap` - &Bitmap
+/// * `callback` - F
+pub(crate) fn execute_face_track_on_bitmap<'a, T, F>(
+    session: *mut c_void,
+    bitmap: &Bitmap,
+    callback: F,
+) -> Result<T, FFIError<'a>>
+where
+    F: FnOnce(*mut c_void, *mut c_void, &mut HFMultipleFaceData) -> Result<T, FFIError<'a>>,
+{
+    let stream = ImageStream::from_bitmap(bitmap)?;
+
+    unsafe {
+        let mut multiple_face_data: HFMultipleFaceData = mem::zeroed();
+
+        if HFExecuteFaceTrack(session, stream.as_ptr(), &mut multiple_face_data).0 != SUCCESS {
+            return Err(FFIError::FaceTrack(""));
+        }
+
+        callback(session, stream.as_ptr(), &mut multiple_face_data)
+    }
+}
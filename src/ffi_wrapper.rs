@@ -17,6 +17,16 @@ include_cpp! {
     generate!("HFCreateFaceFeature")
     generate!("HSUCCEED")
     generate!("HFLaunchInspireFace")
+    generate!("HFReloadInspireFace")
+    generate!("HFTerminateInspireFace")
+    generate!("HFQueryInspireFaceLaunchStatus")
+    generate_pod!("HFInspireFaceVersion")
+    generate!("HFQueryInspireFaceVersion")
+    generate!("baklava_query_extended_information")
+    generate_pod!("HFSimilarityConverterConfig")
+    generate!("HFGetCosineSimilarityConverter")
+    generate!("HFUpdateCosineSimilarityConverter")
+    generate!("HFGetRecommendedCosineThreshold")
     generate!("HFReleaseImageBitmap")
     generate_pod!("HFRotation")
     generate!("HFExecuteFaceTrack")
@@ -28,6 +38,49 @@ include_cpp! {
     generate!("HFloat")
     generate!("HFReleaseInspireFaceSession")
     generate!("HFReleaseFaceFeature")
+    generate!("HF_ENABLE_QUALITY")
+    generate!("HF_ENABLE_FACE_POSE")
+    generate!("HFFaceFeatureExtractCpy")
+    generate!("HFFaceQualityDetect")
+    generate!("HFGetFeatureLength")
+    generate!("HFSessionClearTrackingFace")
+    generate_pod!("HFaceRect")
+    // Accessors over the opaque HFMultipleFaceData, see shim.h
+    generate!("baklava_face_data_detected_num")
+    generate!("baklava_face_data_rect")
+    generate!("baklava_face_data_track_id")
+    generate!("baklava_face_data_det_confidence")
+    generate!("baklava_face_data_roll")
+    generate!("baklava_face_data_yaw")
+    generate!("baklava_face_data_pitch")
+    generate!("baklava_face_feature_copy_from")
+    generate!("baklava_create_image_bitmap")
+    generate!("baklava_face_get_alignment_image")
+    generate_pod!("HFImageBitmapData")
+    generate!("HFImageBitmapGetData")
+    generate!("HFImageBitmapWriteToFile")
+    generate!("baklava_face_feature_extract_with_alignment_image_cpy")
+    generate!("HFGetFaceFiveKeyPointsFromFaceToken")
+    generate!("HFImageBitmapDrawRect")
+    generate!("HFImageBitmapDrawCircle")
+    generate_pod!("HPoint2f")
+    generate_pod!("HPoint2i")
+    generate_pod!("HColor")
+    generate_pod!("HFFeatureHubConfiguration")
+    generate_pod!("HFPKMode")
+    generate_pod!("HFSearchMode")
+    generate_pod!("HFSearchTopKResults")
+    generate!("HFFeatureHubDataEnable")
+    generate!("HFFeatureHubDataDisable")
+    generate!("HFFeatureHubFaceRemove")
+    generate!("HFFeatureHubGetFaceCount")
+    generate!("HFFeatureHubGetExistingIds")
+    generate_pod!("HFFeatureHubExistingIds")
+    generate!("baklava_feature_hub_insert_feature")
+    generate!("baklava_feature_hub_face_update")
+    generate!("baklava_feature_hub_face_search")
+    generate!("baklava_feature_hub_face_search_top_k")
+    generate!("baklava_face_comparison")
 }
 
 pub use ffi::*;
@@ -0,0 +1,466 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    baklava_feature_hub_face_search, baklava_feature_hub_face_search_top_k,
+    baklava_feature_hub_face_update, baklava_feature_hub_insert_feature, HFFeatureHubConfiguration,
+    HFFeatureHubDataDisable, HFFeatureHubDataEnable, HFFeatureHubExistingIds,
+    HFFeatureHubFaceRemove, HFFeatureHubGetExistingIds, HFFeatureHubGetFaceCount, HFPKMode,
+    HFSearchMode, HFSearchTopKResults,
+};
+use crate::runtime::Runtime;
+use crate::similarity::SimilarityConverter;
+use crate::{InsightFace, SUCCESS};
+use autocxx::prelude::*;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// FeatureHub is global to the process. Only one gallery can use it at a time.
+static FEATURE_HUB_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Gallery is a set of enrolled faces stored in the FeatureHub of InspireFace. It answers "who is this?" queries
+/// by searching the enrolled faces which are the most similar to a given face.
+///
+/// FeatureHub is global to the process, hence only one gallery can exist at a time.
+pub struct Gallery {
+    primary_key: PrimaryKeyMode,
+    converter: SimilarityConverter,
+    // Kept alive for as long as FeatureHub is enabled
+    _persistence_path: Option<CString>,
+    // FeatureHub needs to be disabled before the SDK is terminated
+    runtime: Runtime,
+}
+
+/// PrimaryKeyMode defines how the ids of the enrolled faces are assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrimaryKeyMode {
+    /// FeatureHub assigns an incremental id to each enrolled face
+    AutoIncrement,
+    /// The ids are given by the caller when enrolling a face
+    #[default]
+    Manual,
+}
+
+/// SearchMode defines how FeatureHub looks for the most similar face
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Stop as soon as a face is above the search threshold
+    Eager,
+    /// Go through every enrolled face and return the most similar one
+    #[default]
+    Exhaustive,
+}
+
+/// GalleryConfig is the configuration of a gallery
+#[derive(Debug, Clone, Default)]
+pub struct GalleryConfig {
+    /// Path to the SQLite database where the enrolled faces are persisted. The gallery is kept in memory when None.
+    pub persistence_path: Option<String>,
+    pub primary_key: PrimaryKeyMode,
+    pub search_mode: SearchMode,
+    /// Faces whose cosine is below the threshold are never returned. Default to the cosine threshold recommended by
+    /// the loaded model pack.
+    pub search_threshold: Option<f32>,
+}
+
+/// GalleryMatch is an enrolled face returned by a search in the gallery
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GalleryMatch {
+    /// Id under which the face has been enrolled
+    pub id: i64,
+    /// Cosine similarity between the enrolled face and the searched face
+    pub cosine: f32,
+    /// Percentage of similarity computed from the cosine
+    pub percentage: f64,
+}
+
+impl Gallery {
+    /// Create a new in-memory gallery. Faces whose cosine is below the search threshold are never returned.
+    /// The InsightFace handler is required as the model needs to be launched beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &InsightFace
+    /// * `search_threshold` - `Option<f32>`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{Gallery, InsightFace};
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let mut gallery = Gallery::new(&handler, None).unwrap();
+    ///
+    /// gallery.enroll(&mut handler, 1, "./face1_test.png").unwrap();
+    /// let matches = gallery.identify(&mut handler, "./face2_test.png", 3).unwrap();
+    /// ```
+    pub fn new<'a>(
+        handler: &InsightFace,
+        search_threshold: Option<f32>,
+    ) -> Result<Self, FFIError<'a>> {
+        Self::with_config(
+            handler,
+            GalleryConfig {
+                search_threshold,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create a new gallery with the given configuration. When a persistence path is set, the faces enrolled in a
+    /// previous run are loaded back from the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &InsightFace
+    /// * `config` - GalleryConfig
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::gallery::{GalleryConfig, PrimaryKeyMode};
+    /// use baklava::{Gallery, InsightFace};
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let mut gallery = Gallery::with_config(&handler, GalleryConfig {
+    ///     persistence_path: Some("./gallery.db".to_string()),
+    ///     primary_key: PrimaryKeyMode::AutoIncrement,
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// let id = gallery.enroll(&mut handler, 0, "./face1_test.png").unwrap();
+    /// ```
+    pub fn with_config<'a>(
+        handler: &InsightFace,
+        config: GalleryConfig,
+    ) -> Result<Self, FFIError<'a>> {
+        let persistence_path = config
+            .persistence_path
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| FFIError::Gallery("invalid persistence path"))?;
+
+        if FEATURE_HUB_ENABLED
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(FFIError::Gallery("a gallery is already in use"));
+        }
+
+        let configuration = HFFeatureHubConfiguration {
+            primaryKeyMode: match config.primary_key {
+                PrimaryKeyMode::AutoIncrement => HFPKMode::HF_PK_AUTO_INCREMENT,
+                PrimaryKeyMode::Manual => HFPKMode::HF_PK_MANUAL_INPUT,
+            },
+            enablePersistence: persistence_path.is_some() as i32,
+            // FeatureHub does not write to the path
+            persistenceDbPath: persistence_path
+                .as_ref()
+                .map(|path| path.as_ptr() as *mut _)
+                .unwrap_or(std::ptr::null_mut()),
+            searchThreshold: config
+                .search_threshold
+                .unwrap_or(handler.runtime().recommended_threshold()),
+            searchMode: match config.search_mode {
+                SearchMode::Eager => HFSearchMode::HF_SEARCH_MODE_EAGER,
+                SearchMode::Exhaustive => HFSearchMode::HF_SEARCH_MODE_EXHAUSTIVE,
+            },
+        };
+
+        unsafe {
+            if HFFeatureHubDataEnable(configuration).0 != SUCCESS {
+                FEATURE_HUB_ENABLED.store(false, Ordering::SeqCst);
+                return Err(FFIError::Gallery("unable to enable the FeatureHub"));
+            }
+        }
+
+        Ok(Self {
+            primary_key: config.primary_key,
+            converter: *handler.similarity_converter(),
+            _persistence_path: persistence_path,
+            runtime: handler.runtime().clone(),
+        })
+    }
+
+    /// Return the number of enrolled faces
+    pub fn len<'a>(&self) -> Result<usize, FFIError<'a>> {
+        let mut count = c_int(0);
+        unsafe {
+            if HFFeatureHubGetFaceCount(&mut count).0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to count the faces"));
+            }
+        }
+
+        Ok(count.0 as usize)
+    }
+
+    /// Return whether no face has been enrolled
+    pub fn is_empty<'a>(&self) -> Result<bool, FFIError<'a>> {
+        self.len().map(|count| count == 0)
+    }
+
+    /// Return the ids of the enrolled faces. FeatureHub returns them in a buffer global to the process, which is only
+    /// valid until its next call, hence the gallery is borrowed mutably while it's copied.
+    pub fn ids<'a>(&mut self) -> Result<Vec<i64>, FFIError<'a>> {
+        unsafe {
+            let mut existing = HFFeatureHubExistingIds {
+                size: 0,
+                ids: std::ptr::null_mut(),
+            };
+
+            if HFFeatureHubGetExistingIds(&mut existing).0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to list the ids"));
+            }
+
+            if existing.size <= 0 || existing.ids.is_null() {
+                return Ok(vec![]);
+            }
+
+            Ok(std::slice::from_raw_parts(existing.ids, existing.size as usize).to_vec())
+        }
+    }
+
+    /// Enroll the face of an image under the given id and return the id of the face. With `PrimaryKeyMode::AutoIncrement`
+    /// the given id is ignored and the id assigned by FeatureHub is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &mut InsightFace
+    /// * `id` - i64
+    /// * `img_path` - S
+    pub fn enroll<'a, S: AsRef<str>>(
+        &mut self,
+        handler: &mut InsightFace,
+        id: i64,
+        img_path: S,
+    ) -> Result<i64, FFIError<'a>> {
+        let embedding = handler.extract_embedding(img_path)?;
+
+        self.enroll_embedding(id, &embedding)
+    }
+
+    /// Enroll an embedding under the given id and return the id of the face. See `enroll` for how the id is assigned.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - i64
+    /// * `embedding` - &FaceEmbedding
+    pub fn enroll_embedding<'a>(
+        &mut self,
+        id: i64,
+        embedding: &FaceEmbedding,
+    ) -> Result<i64, FFIError<'a>> {
+        let mut data = self.feature_data(embedding)?;
+        let mut alloc_id: i64 = 0;
+
+        unsafe {
+            let res = baklava_feature_hub_insert_feature(
+                id,
+                data.as_mut_ptr(),
+                c_int(data.len() as i32),
+                &mut alloc_id,
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Gallery(
+                    "unable to insert the face, the id may already be enrolled",
+                ));
+            }
+        }
+
+        match self.primary_key {
+            PrimaryKeyMode::AutoIncrement => Ok(alloc_id),
+            PrimaryKeyMode::Manual => Ok(id),
+        }
+    }
+
+    /// Replace the face enrolled under the given id by the face of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &mut InsightFace
+    /// * `id` - i64
+    /// * `img_path` - S
+    pub fn update<'a, S: AsRef<str>>(
+        &mut self,
+        handler: &mut InsightFace,
+        id: i64,
+        img_path: S,
+    ) -> Result<(), FFIError<'a>> {
+        let embedding = handler.extract_embedding(img_path)?;
+
+        self.update_embedding(id, &embedding)
+    }
+
+    /// Replace the face enrolled under the given id by an embedding
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - i64
+    /// * `embedding` - &FaceEmbedding
+    pub fn update_embedding<'a>(
+        &mut self,
+        id: i64,
+        embedding: &FaceEmbedding,
+    ) -> Result<(), FFIError<'a>> {
+        let mut data = self.feature_data(embedding)?;
+
+        unsafe {
+            let res =
+                baklava_feature_hub_face_update(id, data.as_mut_ptr(), c_int(data.len() as i32));
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to update the face"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the face enrolled under the given id
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - i64
+    pub fn remove<'a>(&mut self, id: i64) -> Result<(), FFIError<'a>> {
+        unsafe {
+            if HFFeatureHubFaceRemove(id).0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to remove the face"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return the `top_k` enrolled faces which are the most similar to the face of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &mut InsightFace
+    /// * `img_path` - S
+    /// * `top_k` - usize
+    pub fn identify<'a, S: AsRef<str>>(
+        &mut self,
+        handler: &mut InsightFace,
+        img_path: S,
+        top_k: usize,
+    ) -> Result<Vec<GalleryMatch>, FFIError<'a>> {
+        let embedding = handler.extract_embedding(img_path)?;
+
+        self.search(&embedding, top_k)
+    }
+
+    /// Return the `top_k` enrolled faces which are the most similar to an embedding, sorted by descending cosine.
+    /// FeatureHub writes the results to a buffer global to the process, which is only valid until the next search,
+    /// hence the gallery is borrowed mutably while they are copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - &FaceEmbedding
+    /// * `top_k` - usize
+    pub fn search<'a>(
+        &mut self,
+        embedding: &FaceEmbedding,
+        top_k: usize,
+    ) -> Result<Vec<GalleryMatch>, FFIError<'a>> {
+        let top_k = i32::try_from(top_k).map_err(|_| FFIError::Gallery("top_k is too large"))?;
+        let mut data = self.feature_data(embedding)?;
+
+        unsafe {
+            let mut results = HFSearchTopKResults {
+                size: 0,
+                confidence: std::ptr::null_mut(),
+                ids: std::ptr::null_mut(),
+            };
+
+            let res = baklava_feature_hub_face_search_top_k(
+                data.as_mut_ptr(),
+                c_int(data.len() as i32),
+                c_int(top_k),
+                &mut results,
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to search the faces"));
+            }
+
+            if results.size <= 0 || results.confidence.is_null() || results.ids.is_null() {
+                return Ok(vec![]);
+            }
+
+            let size = results.size as usize;
+            let cosines = std::slice::from_raw_parts(results.confidence, size);
+            let ids = std::slice::from_raw_parts(results.ids, size);
+
+            let mut matches = ids
+                .iter()
+                .zip(cosines.iter())
+                .map(|(id, cosine)| GalleryMatch {
+                    id: *id,
+                    cosine: *cosine,
+                    percentage: self.converter.percentage(*cosine),
+                })
+                .collect::<Vec<_>>();
+
+            matches.sort_by(|a, b| b.cosine.total_cmp(&a.cosine));
+
+            Ok(matches)
+        }
+    }
+
+    /// Return the enrolled face which is the most similar to an embedding if its cosine is above the search threshold.
+    /// The gallery is borrowed mutably for the same reason as `search`.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - &FaceEmbedding
+    pub fn search_best<'a>(
+        &mut self,
+        embedding: &FaceEmbedding,
+    ) -> Result<Option<GalleryMatch>, FFIError<'a>> {
+        let mut data = self.feature_data(embedding)?;
+        let mut cosine: f32 = 0.0;
+        let mut id: i64 = -1;
+
+        unsafe {
+            let res = baklava_feature_hub_face_search(
+                data.as_mut_ptr(),
+                c_int(data.len() as i32),
+                &mut cosine,
+                &mut id,
+            );
+
+            if res.0 != SUCCESS {
+                return Err(FFIError::Gallery("unable to search the faces"));
+            }
+        }
+
+        if id == -1 {
+            return Ok(None);
+        }
+
+        Ok(Some(GalleryMatch {
+            id,
+            cosine,
+            percentage: self.converter.percentage(cosine),
+        }))
+    }
+
+    /// Return a copy of the feature vector of the embedding which is handed to FeatureHub
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - &FaceEmbedding
+    fn feature_data<'a>(&self, embedding: &FaceEmbedding) -> Result<Vec<f32>, FFIError<'a>> {
+        embedding.check_model(&self.runtime.model_info())?;
+
+        Ok(embedding.as_slice().to_vec())
+    }
+}
+
+impl Drop for Gallery {
+    fn drop(&mut self) {
+        unsafe {
+            HFFeatureHubDataDisable();
+        }
+
+        FEATURE_HUB_ENABLED.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::asynchronous::AsyncInsightFace;
+use crate::error::FFIError;
+use crate::face::DetectedFace;
+use crate::image::FaceImage;
+use crate::index::IdentityIndex;
+use crate::tracking::TrackingSession;
+use crate::{InsightFace, Methodology, TRACKING_MAX_FACES};
+use proto::baklava_server::{Baklava, BaklavaServer};
+use proto::image::Source;
+use proto::{
+    DetectRequest, DetectResponse, EmbedRequest, EmbedResponse, IdentifyRequest, IdentifyResponse,
+    IdentityMatch, TrackedFrame, VerifyRequest, VerifyResponse, VideoFrame,
+};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Messages and service generated from `proto/baklava.proto`
+pub mod proto {
+    tonic::include_proto!("baklava.v1");
+}
+
+// Number of tracked frames buffered per video stream. Reading the frames of the client is paused once it's full.
+const TRACKED_FRAMES_BUFFER: usize = 4;
+// Maximum number of identities returned by `Identify` unless configured otherwise
+const DEFAULT_MAX_TOP_K: usize = 100;
+// Maximum number of sources images of `Verify` unless configured otherwise
+const DEFAULT_MAX_IMAGES: usize = 16;
+// Maximum number of video streams tracked at the same time unless configured otherwise
+const DEFAULT_MAX_STREAMS: usize = 8;
+
+/// BaklavaService implements the `Baklava` gRPC service over a shared handler. The unary calls are dispatched to the
+/// blocking pool of tokio, at most one per session of the pool of the handler, the other calls wait for a session.
+///
+/// Each video stream gets its own tracking session, at most `max_streams` at the same time. Its frames are read on
+/// the runtime and tracked one at a time on the blocking pool.
+pub struct BaklavaService {
+    handler: AsyncInsightFace,
+    gallery: Arc<IdentityIndex<String>>,
+    max_images: usize,
+    max_top_k: usize,
+    streams: Arc<Semaphore>,
+}
+
+impl BaklavaService {
+    /// Create a new service. The identities of the gallery are the ones searched by `Identify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - InsightFace
+    /// * `gallery` - `IdentityIndex<String>`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::grpc::BaklavaService;
+    /// use baklava::{IdentityIndex, InsightFace};
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = InsightFace::with_session_pool("./Megatron", None, 4)?;
+    /// let gallery = IdentityIndex::new().with_converter(*handler.similarity_converter());
+    /// let service = BaklavaService::new(handler, gallery);
+    ///
+    /// tonic::transport::Server::builder()
+    ///     .add_service(service.into_server())
+    ///     .serve("127.0.0.1:50051".parse()?)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(handler: InsightFace, gallery: IdentityIndex<String>) -> Self {
+        let pool_size = handler.pool_size();
+
+        Self {
+            handler: AsyncInsightFace::new(handler, pool_size),
+            gallery: Arc::new(gallery),
+            max_images: DEFAULT_MAX_IMAGES,
+            max_top_k: DEFAULT_MAX_TOP_K,
+            streams: Arc::new(Semaphore::new(DEFAULT_MAX_STREAMS)),
+        }
+    }
+
+    /// Set the maximum number of sources images a client can send to `Verify`. Default to 16.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_images` - usize
+    pub fn with_max_images(mut self, max_images: usize) -> Self {
+        self.max_images = max_images;
+        self
+    }
+
+    /// Set the maximum number of identities a client can request from `Identify`. Default to 100.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_top_k` - usize
+    pub fn with_max_top_k(mut self, max_top_k: usize) -> Self {
+        self.max_top_k = max_top_k;
+        self
+    }
+
+    /// Set the maximum number of video streams tracked at the same time. Default to 8.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_streams` - usize
+    pub fn with_max_streams(mut self, max_streams: usize) -> Self {
+        self.streams = Arc::new(Semaphore::new(max_streams.max(1)));
+        self
+    }
+
+    /// Wrap the service into the tonic server
+    pub fn into_server(self) -> BaklavaServer<Self> {
+        BaklavaServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl Baklava for BaklavaService {
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let request = request.into_inner();
+        if request.sources.is_empty() || request.sources.len() > self.max_images {
+            return Err(Status::invalid_argument(format!(
+                "between 1 and {} sources are expected",
+                self.max_images
+            )));
+        }
+
+        let methodology = match request.methodology() {
+            proto::Methodology::Mean => Methodology::Mean,
+            proto::Methodology::Median => Methodology::Median,
+        };
+
+        self.handler
+            .run(move |handler| {
+                let sources = request
+                    .sources
+                    .into_iter()
+                    .map(|source| Ok(handler.extract_image(&decode_image(Some(source))?)?))
+                    .collect::<Result<Vec<_>, Status>>()?;
+                let target = handler.extract_image(&decode_image(request.target)?)?;
+
+                let (cosine, percentage) = handler.compare(&sources, &target, methodology)?;
+
+                Ok(VerifyResponse {
+                    cosine,
+                    percentage,
+                    similar: handler.is_similar(cosine, request.threshold),
+                })
+            })
+            .await
+            .map(Response::new)
+    }
+
+    async fn embed(
+        &self,
+        request: Request<EmbedRequest>,
+    ) -> Result<Response<EmbedResponse>, Status> {
+        let image = request.into_inner().image;
+
+        self.handler
+            .run(move |handler| {
+                let embedding = handler.extract_image(&decode_image(image)?)?;
+
+                Ok(EmbedResponse {
+                    model: embedding
+                        .model()
+                        .map(|model| model.pack.clone())
+                        .unwrap_or_default(),
+                    embedding: embedding.as_slice().to_vec(),
+                })
+            })
+            .await
+            .map(Response::new)
+    }
+
+    async fn detect(
+        &self,
+        request: Request<DetectRequest>,
+    ) -> Result<Response<DetectResponse>, Status> {
+        let image = request.into_inner().image;
+
+        self.handler
+            .run(move |handler| {
+                let faces = handler.detect_faces_in_image(&decode_image(image)?)?;
+
+                Ok(DetectResponse {
+                    faces: faces.iter().map(face_to_proto).collect(),
+                })
+            })
+            .await
+            .map(Response::new)
+    }
+
+    async fn identify(
+        &self,
+        request: Request<IdentifyRequest>,
+    ) -> Result<Response<IdentifyResponse>, Status> {
+        if self.gallery.is_empty() {
+            return Err(Status::failed_precondition("no identity has been enrolled"));
+        }
+
+        let request = request.into_inner();
+        let top_k = match request.top_k as usize {
+            0 => 1,
+            top_k if top_k <= self.max_top_k => top_k,
+            _ => {
+                return Err(Status::invalid_argument(format!(
+                    "top_k needs to be at most {}",
+                    self.max_top_k
+                )))
+            }
+        };
+        let gallery = self.gallery.clone();
+
+        self.handler
+            .run(move |handler| {
+                let probe = handler.extract_image(&decode_image(request.image)?)?;
+
+                let matches = gallery
+                    .search(&probe, top_k)?
+                    .into_iter()
+                    .map(|candidate| IdentityMatch {
+                        identity: candidate.key,
+                        cosine: candidate.cosine,
+                        percentage: candidate.percentage,
+                        similar: handler.is_similar(candidate.cosine, request.threshold),
+                    })
+                    .collect();
+
+                Ok(IdentifyResponse { matches })
+            })
+            .await
+            .map(Response::new)
+    }
+
+    type TrackVideoStream = ReceiverStream<Result<TrackedFrame, Status>>;
+
+    async fn track_video(
+        &self,
+        request: Request<Streaming<VideoFrame>>,
+    ) -> Result<Response<Self::TrackVideoStream>, Status> {
+        let permit = Arc::clone(&self.streams)
+            .try_acquire_owned()
+            .map_err(|_| Status::resource_exhausted("too many video streams are being tracked"))?;
+
+        let mut frames = request.into_inner();
+        let mut session = TrackingSession::new(self.handler.handler(), TRACKING_MAX_FACES)?;
+        let (sender, receiver) = mpsc::channel(TRACKED_FRAMES_BUFFER);
+
+        tokio::spawn(async move {
+            // The slot of the stream is released once the task is over
+            let _permit = permit;
+
+            while let Some(frame) = frames.message().await.transpose() {
+                let tracked = match frame {
+                    Ok(frame) => {
+                        // The session is moved to the blocking pool for the frame and handed back afterwards
+                        let job = tokio::task::spawn_blocking(move || {
+                            let tracked = track_frame(&mut session, frame);
+                            (session, tracked)
+                        });
+
+                        match job.await {
+                            Ok((tracked_session, tracked)) => {
+                                session = tracked_session;
+                                tracked
+                            }
+                            Err(err) => {
+                                let _ = sender.send(Err(Status::internal(err.to_string()))).await;
+                                break;
+                            }
+                        }
+                    }
+                    Err(status) => Err(status),
+                };
+
+                // The stream ends with the first error or once the client went away
+                let failed = tracked.is_err();
+                if sender.send(tracked).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+impl From<FFIError<'_>> for Status {
+    fn from(err: FFIError<'_>) -> Self {
+        match err {
+            FFIError::Session | FFIError::Async(_) | FFIError::Runtime(_) => {
+                Status::internal(err.to_string())
+            }
+            _ => Status::invalid_argument(err.to_string()),
+        }
+    }
+}
+
+/// Track the faces of a frame of a video stream
+///
+/// # Arguments
+///
+/// * `session` - `&mut TrackingSession`
+/// * `frame` - VideoFrame
+fn track_frame(session: &mut TrackingSession, frame: VideoFrame) -> Result<TrackedFrame, Status> {
+    let faces = session.track_image(&decode_image(frame.image)?)?;
+
+    Ok(TrackedFrame {
+        index: frame.index,
+        faces: faces.iter().map(face_to_proto).collect(),
+    })
+}
+
+/// Decode the image of a message, which is either an encoded file or raw pixels
+///
+/// # Arguments
+///
+/// * `image` - `Option<proto::Image>`
+fn decode_image(image: Option<proto::Image>) -> Result<FaceImage, Status> {
+    let image = match image.and_then(|image| image.source) {
+        Some(Source::Encoded(encoded)) => FaceImage::decode(&encoded)?,
+        Some(Source::Raw(raw)) => FaceImage::new(raw.width, raw.height, raw.channels, raw.data)?,
+        None => return Err(Status::invalid_argument("an image is required")),
+    };
+
+    Ok(image)
+}
+
+fn face_to_proto(face: &DetectedFace) -> proto::Face {
+    proto::Face {
+        track_id: face.track_id,
+        rect: Some(proto::Rect {
+            x: face.rect.x,
+            y: face.rect.y,
+            width: face.rect.width,
+            height: face.rect.height,
+        }),
+        confidence: face.confidence,
+        pose: Some(proto::Pose {
+            roll: face.pose.roll,
+            yaw: face.pose.yaw,
+            pitch: face.pose.pitch,
+        }),
+        landmarks: face
+            .landmarks
+            .iter()
+            .map(|point| proto::Point {
+                x: point.x,
+                y: point.y,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_image;
+    use super::proto::image::Source;
+    use super::proto::{Image, RawImage};
+    use tonic::Code;
+
+    #[test]
+    fn expect_raw_images_to_be_decoded() {
+        let raw = |data: Vec<u8>| Image {
+            source: Some(Source::Raw(RawImage {
+                width: 2,
+                height: 2,
+                channels: 3,
+                data,
+            })),
+        };
+
+        let image = decode_image(Some(raw(vec![1; 12]))).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+
+        let err = decode_image(Some(raw(vec![1; 11]))).unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        let err = decode_image(None).unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn expect_overflowing_raw_images_to_be_rejected() {
+        // 65536 * 65537 wraps to the length of the data in i32
+        let image = Image {
+            source: Some(Source::Raw(RawImage {
+                width: 65536,
+                height: 65537,
+                channels: 1,
+                data: vec![0; 65536],
+            })),
+        };
+
+        let err = decode_image(Some(image)).unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,210 @@
+//! RAII wrappers around the handles of InspireFace. Each handle is released when its wrapper is dropped, hence
+//! early returns and errors can't leak them.
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    baklava_create_image_bitmap, baklava_create_image_bitmap_from_path,
+    baklava_create_image_stream_from_bitmap, baklava_create_session_optional, HFCreateFaceFeature,
+    HFDetectMode, HFFaceFeature, HFImageBitmapData, HFReleaseFaceFeature, HFReleaseImageBitmap,
+    HFReleaseImageStream, HFReleaseInspireFaceSession, HFRotation,
+};
+use crate::SUCCESS;
+use autocxx::prelude::*;
+use autocxx::{c_long, c_void};
+use std::ffi::CStr;
+use std::mem;
+
+/// Bitmap is an image loaded by InspireFace
+pub(crate) struct Bitmap(*mut c_void);
+
+impl Bitmap {
+    /// Load a 3 channels bitmap from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - &CStr
+    pub(crate) fn from_path<'a>(path: &CStr) -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut result = c_long(0);
+            let ptr = baklava_create_image_bitmap_from_path(path.as_ptr(), c_int(3), &mut result);
+
+            Self::from_result(ptr, result)
+        }
+    }
+
+    /// Create a bitmap holding a copy of the pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - &mut HFImageBitmapData
+    pub(crate) fn from_data<'a>(data: &mut HFImageBitmapData) -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut result = c_long(0);
+            let ptr = baklava_create_image_bitmap(data, &mut result);
+
+            Self::from_result(ptr, result)
+        }
+    }
+
+    /// Take the ownership of a bitmap returned by the SDK. The bitmap is released even if the result is a failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - *mut c_void
+    /// * `result` - c_long
+    pub(crate) fn from_result<'a>(ptr: *mut c_void, result: c_long) -> Result<Self, FFIError<'a>> {
+        let bitmap = Self(ptr);
+        if result.0 != SUCCESS || ptr.is_null() {
+            return Err(FFIError::Bitmap(
+                "image may not be the proper size or format",
+            ));
+        }
+
+        Ok(bitmap)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Drop for Bitmap {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe {
+                HFReleaseImageBitmap(self.0);
+            }
+        }
+    }
+}
+
+/// ImageStream is the stream of a bitmap which is consumed by the face tracking and the feature extraction
+pub(crate) struct ImageStream(*mut c_void);
+
+impl ImageStream {
+    /// Create a stream out of a bitmap which is not rotated
+    ///
+    /// # Arguments
+    ///
+    /// * `bitmap` - &Bitmap
+    pub(crate) fn from_bitmap<'a>(bitmap: &Bitmap) -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut result = c_long(0);
+            let stream = Self(baklava_create_image_stream_from_bitmap(
+                bitmap.as_ptr(),
+                HFRotation::HF_CAMERA_ROTATION_0,
+                &mut result,
+            ));
+
+            if result.0 != SUCCESS || stream.0.is_null() {
+                return Err(FFIError::Stream(
+                    "Unable to create stream issue with rotation",
+                ));
+            }
+
+            Ok(stream)
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Drop for ImageStream {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe {
+                HFReleaseImageStream(self.0);
+            }
+        }
+    }
+}
+
+/// Feature is a feature vector allocated by InspireFace with the feature length of the loaded model
+pub(crate) struct Feature(HFFaceFeature);
+
+// The feature data is only reachable through the wrapper.
+unsafe impl Send for Feature {}
+unsafe impl Sync for Feature {}
+
+impl Feature {
+    pub(crate) fn new<'a>() -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut feature: HFFaceFeature = mem::zeroed();
+            if HFCreateFaceFeature(&mut feature).0 != SUCCESS {
+                return Err(FFIError::Feature);
+            }
+
+            Ok(Self(feature))
+        }
+    }
+
+    pub(crate) fn as_raw(&self) -> &HFFaceFeature {
+        &self.0
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut HFFaceFeature {
+        &mut self.0
+    }
+}
+
+impl Drop for Feature {
+    fn drop(&mut self) {
+        unsafe {
+            HFReleaseFaceFeature(&mut self.0);
+        }
+    }
+}
+
+/// Session is an InspireFace session. A session must not be used by several threads at the same time.
+pub(crate) struct Session(*mut c_void);
+
+// The pointer is not shared with anything else, the exclusive access is up to the owner.
+unsafe impl Send for Session {}
+
+impl Session {
+    /// Create a new session
+    ///
+    /// # Arguments
+    ///
+    /// * `option` - u32
+    /// * `detect_mode` - HFDetectMode
+    /// * `max_faces` - i32
+    pub(crate) fn new<'a>(
+        option: u32,
+        detect_mode: HFDetectMode,
+        max_faces: i32,
+    ) -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut result = c_long(0);
+            let session = Self(baklava_create_session_optional(
+                c_int(option as i32),
+                detect_mode,
+                c_int(max_faces),
+                c_int(-1),
+                c_int(-1),
+                &mut result,
+            ));
+
+            if result.0 != SUCCESS || session.0.is_null() {
+                return Err(FFIError::Session);
+            }
+
+            Ok(session)
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe {
+                HFReleaseInspireFaceSession(self.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::FFIError;
+use crate::ffi_wrapper::{HFImageBitmapData, HFImageBitmapGetData, HFImageBitmapWriteToFile};
+use crate::handle::Bitmap;
+use crate::SUCCESS;
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{env, process};
+
+// Number of temporary files created so far, used to name them
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+// Number of names tried before giving up on creating a temporary file
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// FaceImage is an in-memory image using the pixel layout of InspireFace bitmaps (BGR for 3 channels, gray for 1 channel).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaceImage {
+    width: i32,
+    height: i32,
+    channels: i32,
+    data: Vec<u8>,
+}
+
+impl FaceImage {
+    /// Create a new image from raw pixels. Only 3 channels (BGR) or 1 channel (gray) images are supported by InspireFace.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - i32
+    /// * `height` - i32
+    /// * `channels` - i32
+    /// * `data` - `Vec<u8>`
+    pub fn new<'a>(
+        width: i32,
+        height: i32,
+        channels: i32,
+        data: Vec<u8>,
+    ) -> Result<Self, FFIError<'a>> {
+        if channels != 3 && channels != 1 {
+            return Err(FFIError::Bitmap(
+                "only 3 channels or 1 channel are supported",
+            ));
+        }
+
+        if Self::data_len(width, height, channels) != Some(data.len()) {
+            return Err(FFIError::Bitmap(
+                "pixel data does not match the image dimension",
+            ));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            channels,
+            data,
+        })
+    }
+
+    /// Load an image from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - S
+    pub fn from_path<'a, S: AsRef<str>>(path: S) -> Result<Self, FFIError<'a>> {
+        let path = CString::new(path.as_ref()).map_err(|_| FFIError::MissingImage)?;
+
+        Self::from_bitmap(&Bitmap::from_path(&path)?)
+    }
+
+    /// Decode an encoded image (e.g. the content of a PNG or a JPEG file). InspireFace only decodes files, hence the
+    /// content is written to a temporary file which is removed once decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded` - &[u8]
+    pub fn decode<'a>(encoded: &[u8]) -> Result<Self, FFIError<'a>> {
+        let file = TempFile::create(encoded)
+            .map_err(|_| FFIError::IO("Unable to write the encoded image"))?;
+
+        Self::from_path(file.0.to_string_lossy())
+    }
+
+    /// Return the width of the image
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Return the height of the image
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Return the number of channels of the image
+    pub fn channels(&self) -> i32 {
+        self.channels
+    }
+
+    /// Return the number of bytes of an image, or None when a dimension is not positive or the size overflows
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - i32
+    /// * `height` - i32
+    /// * `channels` - i32
+    pub fn data_len(width: i32, height: i32, channels: i32) -> Option<usize> {
+        if width <= 0 || height <= 0 || channels <= 0 {
+            return None;
+        }
+
+        (width as usize)
+            .checked_mul(height as usize)?
+            .checked_mul(channels as usize)
+    }
+
+    /// Return the raw pixels of the image
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the image and return the raw pixels
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Write the image to a file. The format is deduced from the extension of the path (e.g. png, jpg).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - S
+    pub fn write_to_file<'a, S: AsRef<str>>(&self, path: S) -> Result<(), FFIError<'a>> {
+        let path = CString::new(path.as_ref()).map_err(|_| FFIError::IO("Invalid output path"))?;
+        let bitmap = self.to_bitmap()?;
+
+        unsafe {
+            if HFImageBitmapWriteToFile(bitmap.as_ptr(), path.as_ptr()).0 != SUCCESS {
+                return Err(FFIError::IO("Unable to write the image to the file"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the color of a pixel. Gray images get the mean of the color. Pixels outside of the image are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - i32
+    /// * `y` - i32
+    /// * `bgr` - [u8; 3]
+    pub(crate) fn set_pixel(&mut self, x: i32, y: i32, bgr: [u8; 3]) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+
+        let offset = (y as usize * self.width as usize + x as usize) * self.channels as usize;
+        match self.channels {
+            3 => self.data[offset..offset + 3].copy_from_slice(&bgr),
+            _ => self.data[offset] = (bgr.iter().map(|c| *c as u16).sum::<u16>() / 3) as u8,
+        }
+    }
+
+    /// Create an image by copying the pixels of a bitmap
+    ///
+    /// # Arguments
+    ///
+    /// * `bitmap` - &Bitmap
+    pub(crate) fn from_bitmap<'a>(bitmap: &Bitmap) -> Result<Self, FFIError<'a>> {
+        unsafe {
+            let mut bitmap_data = HFImageBitmapData {
+                data: std::ptr::null_mut(),
+                width: 0,
+                height: 0,
+                channels: 0,
+            };
+
+            if HFImageBitmapGetData(bitmap.as_ptr(), &mut bitmap_data).0 != SUCCESS
+                || bitmap_data.data.is_null()
+            {
+                return Err(FFIError::Bitmap("unable to read the bitmap data"));
+            }
+
+            let len = Self::data_len(bitmap_data.width, bitmap_data.height, bitmap_data.channels)
+                .ok_or(FFIError::Bitmap("the bitmap has an invalid dimension"))?;
+            let data = std::slice::from_raw_parts(bitmap_data.data, len).to_vec();
+
+            Self::new(
+                bitmap_data.width,
+                bitmap_data.height,
+                bitmap_data.channels,
+                data,
+            )
+        }
+    }
+
+    /// Create a bitmap out of the image. The bitmap owns a copy of the pixels.
+    pub(crate) fn to_bitmap<'a>(&self) -> Result<Bitmap, FFIError<'a>> {
+        // InspireFace copies the pixels when creating the bitmap, hence the data is never written.
+        let mut bitmap_data = HFImageBitmapData {
+            data: self.data.as_ptr() as *mut u8,
+            width: self.width,
+            height: self.height,
+            channels: self.channels,
+        };
+
+        Bitmap::from_data(&mut bitmap_data)
+    }
+}
+
+/// TempFile is a file written for the SDK to read it, which is removed once dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Create a new temporary file with the content. The file is always created by this call and only readable by
+    /// its owner, hence a file or a symbolic link planted at its path beforehand is never written through.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - &[u8]
+    fn create(content: &[u8]) -> io::Result<Self> {
+        for _ in 0..TEMP_FILE_ATTEMPTS {
+            let path = env::temp_dir().join(format!(
+                "baklava-{}-{}",
+                process::id(),
+                TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp = Self(path);
+                    file.write_all(content)?;
+
+                    return Ok(temp);
+                }
+                // Another process took the name, the next one is tried
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            "no temporary file name is available",
+        ))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaceImage, TempFile, TEMP_FILES};
+    use std::sync::atomic::Ordering;
+    use std::{env, fs, process};
+
+    #[test]
+    fn expect_image_with_wrong_dimension_to_fail() {
+        assert!(FaceImage::new(2, 2, 3, vec![0; 12]).is_ok());
+        assert!(FaceImage::new(2, 2, 3, vec![0; 11]).is_err());
+        assert!(FaceImage::new(2, 2, 4, vec![0; 16]).is_err());
+        assert!(FaceImage::new(0, 2, 3, vec![]).is_err());
+    }
+
+    #[test]
+    fn expect_overflowing_dimension_to_fail() {
+        // 65536 * 65537 wraps to 65536 in i32
+        assert!(FaceImage::new(65536, 65537, 1, vec![0; 65536]).is_err());
+        assert!(FaceImage::new(i32::MAX, i32::MAX, 3, vec![0; 3]).is_err());
+    }
+
+    #[test]
+    fn expect_temp_file_to_never_reuse_an_existing_path() {
+        // Plant a file at the next name
+        let planted = env::temp_dir().join(format!(
+            "baklava-{}-{}",
+            process::id(),
+            TEMP_FILES.load(Ordering::Relaxed)
+        ));
+        fs::write(&planted, b"planted").unwrap();
+
+        let file = TempFile::create(b"image").unwrap();
+        assert_ne!(file.0, planted);
+        assert_eq!(fs::read(&file.0).unwrap(), b"image");
+        assert_eq!(fs::read(&planted).unwrap(), b"planted");
+
+        let path = file.0.clone();
+        drop(file);
+        assert!(fs::metadata(path).is_err());
+        let _ = fs::remove_file(planted);
+    }
+}
@@ -0,0 +1,793 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::model::ModelInfo;
+use crate::similarity::SimilarityConverter;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::Hash;
+
+// Number of floats accumulated side by side by the dot product so that the compiler emits SIMD instructions
+const LANES: usize = 8;
+// The index is compacted once the removed entries outnumber the live ones
+const MIN_TOMBSTONES_BEFORE_COMPACTION: usize = 64;
+
+/// IndexMatch is an entry of the index returned by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexMatch<K> {
+    /// Key under which the embedding has been inserted
+    pub key: K,
+    /// Cosine similarity between the inserted embedding and the searched embedding
+    pub cosine: f32,
+    /// Percentage of similarity computed from the cosine with the converter of the index
+    pub percentage: f64,
+}
+
+/// HnswConfig is the configuration of the approximate search of the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswConfig {
+    /// Number of neighbors linked to each entry. The bottom layer links twice as many.
+    pub max_neighbors: usize,
+    /// Number of candidates explored when inserting an entry
+    pub ef_construction: usize,
+    /// Number of candidates explored when searching. Raised to top_k when lower.
+    pub ef_search: usize,
+    /// Below this number of entries the search is exhaustive as it's both exact and fast enough
+    pub brute_force_limit: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            max_neighbors: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            brute_force_limit: 1_000,
+        }
+    }
+}
+
+/// IdentityIndex is an in-memory index of embeddings identified by a key. Unlike the `Gallery` it does not rely on
+/// the FeatureHub of InspireFace, hence any number of indexes can exist and they can be searched concurrently
+/// from several threads (e.g. behind an `Arc<RwLock<IdentityIndex<K>>>`).
+///
+/// The embeddings are L2-normalized when inserted so that the cosine is a plain dot product. By default the search
+/// goes through every entry, `with_hnsw` builds an HNSW graph to answer approximate searches on large sets.
+#[derive(Debug, Clone)]
+pub struct IdentityIndex<K> {
+    dimension: Option<usize>,
+    // Model pack of the first tagged embedding inserted
+    model: Option<ModelInfo>,
+    vectors: Vec<f32>,
+    // Slots of removed entries are None until the index is compacted
+    keys: Vec<Option<K>>,
+    slots: HashMap<K, usize>,
+    graph: Option<Hnsw>,
+    converter: SimilarityConverter,
+}
+
+impl<K: Eq + Hash + Clone> Default for IdentityIndex<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone> IdentityIndex<K> {
+    /// Create a new index searched exhaustively. The percentages are computed with the default `SimilarityConverter`
+    /// unless the converter of a handler is given with `with_converter`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{IdentityIndex, InsightFace};
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let mut index = IdentityIndex::new().with_converter(*handler.similarity_converter());
+    ///
+    /// index.insert("alice", &handler.extract_embedding("./face1_test.png").unwrap()).unwrap();
+    ///
+    /// let probe = handler.extract_embedding("./face2_test.png").unwrap();
+    /// let matches = index.search(&probe, 3).unwrap();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            dimension: None,
+            model: None,
+            vectors: Vec::new(),
+            keys: Vec::new(),
+            slots: HashMap::new(),
+            graph: None,
+            converter: SimilarityConverter::default(),
+        }
+    }
+
+    /// Create a new index searched approximately through an HNSW graph once it holds more than
+    /// `config.brute_force_limit` entries
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - HnswConfig
+    pub fn with_hnsw(config: HnswConfig) -> Self {
+        Self {
+            graph: Some(Hnsw::new(config)),
+            ..Self::new()
+        }
+    }
+
+    /// Set the converter used to compute the percentage of the matches, usually the one of the handler which
+    /// extracted the embeddings
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - SimilarityConverter
+    pub fn with_converter(mut self, converter: SimilarityConverter) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// Return the converter used to compute the percentage of the matches
+    pub fn similarity_converter(&self) -> &SimilarityConverter {
+        &self.converter
+    }
+
+    /// Return the number of entries of the index
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return whether the index has no entry
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Return whether an entry exists for the key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - &K
+    pub fn contains(&self, key: &K) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    /// Return the keys of the entries of the index
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.slots.keys()
+    }
+
+    /// Insert an embedding under the given key. The embedding previously inserted under the key is replaced.
+    /// All the embeddings must have the same dimension and be extracted by the same model pack.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - K
+    /// * `embedding` - &FaceEmbedding
+    pub fn insert<'a>(&mut self, key: K, embedding: &FaceEmbedding) -> Result<(), FFIError<'a>> {
+        let vector = self.normalize(embedding)?;
+        // The dimension is only known once the first embedding is inserted
+        self.dimension.get_or_insert(vector.len());
+        if self.model.is_none() {
+            self.model = embedding.model().cloned();
+        }
+
+        self.remove(&key);
+        let slot = self.keys.len();
+        self.vectors.extend_from_slice(&vector);
+        self.keys.push(Some(key.clone()));
+        self.slots.insert(key, slot);
+
+        if let Some(mut graph) = self.graph.take() {
+            graph.insert(self, slot);
+            self.graph = Some(graph);
+        }
+
+        Ok(())
+    }
+
+    /// Remove the entry of the key. Return whether an entry existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - &K
+    pub fn remove(&mut self, key: &K) -> bool {
+        let Some(slot) = self.slots.remove(key) else {
+            return false;
+        };
+
+        // The slot is kept as the HNSW graph may route searches through it
+        self.keys[slot] = None;
+
+        let tombstones = self.keys.len() - self.slots.len();
+        if tombstones >= MIN_TOMBSTONES_BEFORE_COMPACTION && tombstones > self.slots.len() {
+            self.compact();
+        }
+
+        true
+    }
+
+    /// Search the entries which are the most similar to the embedding. The matches are sorted from the most
+    /// similar to the least similar.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - &FaceEmbedding
+    /// * `top_k` - usize
+    pub fn search<'a>(
+        &self,
+        embedding: &FaceEmbedding,
+        top_k: usize,
+    ) -> Result<Vec<IndexMatch<K>>, FFIError<'a>> {
+        if self.is_empty() || top_k == 0 {
+            return Ok(vec![]);
+        }
+
+        // No more than the live entries can be returned, which also bounds the buffers of both searches
+        let top_k = top_k.min(self.len());
+        let query = self.normalize(embedding)?;
+        let candidates = match &self.graph {
+            Some(graph) if self.len() > graph.config.brute_force_limit => {
+                graph.search(self, &query, top_k)
+            }
+            _ => self.brute_force(&query, top_k),
+        };
+
+        Ok(candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let key = self.keys[candidate.slot].clone()?;
+
+                Some(IndexMatch {
+                    key,
+                    cosine: candidate.similarity,
+                    percentage: self.converter.percentage(candidate.similarity),
+                })
+            })
+            .collect())
+    }
+
+    /// Return the L2-normalized vector of the embedding after checking its dimension and its model pack
+    ///
+    /// # Arguments
+    ///
+    /// * `embedding` - &FaceEmbedding
+    fn normalize<'a>(&self, embedding: &FaceEmbedding) -> Result<Vec<f32>, FFIError<'a>> {
+        if embedding.is_empty() {
+            return Err(FFIError::Index("the embedding is empty"));
+        }
+
+        if self.dimension.is_some_and(|dim| dim != embedding.len()) {
+            return Err(FFIError::Index(
+                "the embedding does not have the dimension of the index",
+            ));
+        }
+
+        if let (Some(model), Some(tag)) = (&self.model, embedding.model()) {
+            if model != tag {
+                return Err(FFIError::Index(
+                    "the embedding has been extracted by another model pack",
+                ));
+            }
+        }
+
+        let data = embedding.as_slice();
+        let norm = dot(data, data).sqrt();
+        if norm == 0. || !norm.is_finite() {
+            return Err(FFIError::Index("the embedding can not be normalized"));
+        }
+
+        Ok(data.iter().map(|v| v / norm).collect())
+    }
+
+    /// Compare the query against every live entry and keep the top_k most similar
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - &[f32]
+    /// * `top_k` - usize
+    fn brute_force(&self, query: &[f32], top_k: usize) -> Vec<Candidate> {
+        let mut best = BinaryHeap::with_capacity(top_k + 1);
+        for &slot in self.slots.values() {
+            best.push(Reverse(Candidate {
+                similarity: dot(query, self.vector(slot)),
+                slot,
+            }));
+
+            if best.len() > top_k {
+                best.pop();
+            }
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(candidate)| candidate)
+            .collect()
+    }
+
+    /// Drop the removed entries and rebuild the HNSW graph out of the live ones
+    fn compact(&mut self) {
+        let entries = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, key)| Some((key.clone()?, self.vector(slot).to_vec())))
+            .collect::<Vec<_>>();
+
+        let graph = self.graph.as_ref().map(|graph| Hnsw::new(graph.config));
+        let dimension = self.dimension;
+        let model = self.model.take();
+        *self = Self {
+            graph,
+            converter: self.converter,
+            ..Self::new()
+        };
+        self.dimension = dimension;
+        self.model = model;
+
+        for (key, vector) in entries {
+            let slot = self.keys.len();
+            self.vectors.extend_from_slice(&vector);
+            self.keys.push(Some(key.clone()));
+            self.slots.insert(key, slot);
+
+            if let Some(mut graph) = self.graph.take() {
+                graph.insert(self, slot);
+                self.graph = Some(graph);
+            }
+        }
+    }
+}
+
+// Accessors shared with the HNSW graph which does not need to know about the keys
+impl<K> IdentityIndex<K> {
+    /// Return the normalized vector stored in the slot
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - usize
+    fn vector(&self, slot: usize) -> &[f32] {
+        let dimension = self.dimension.unwrap_or_default();
+
+        &self.vectors[slot * dimension..(slot + 1) * dimension]
+    }
+}
+
+/// Candidate is an entry of the index along with its similarity to the query. Candidates are ordered by similarity.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    similarity: f32,
+    slot: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then_with(|| other.slot.cmp(&self.slot))
+    }
+}
+
+/// Hnsw is a Hierarchical Navigable Small World graph over the slots of the index
+#[derive(Debug, Clone)]
+struct Hnsw {
+    config: HnswConfig,
+    // Neighbors of each slot for each of its layers, the bottom layer first
+    links: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    level_factor: f64,
+    rng_state: u64,
+}
+
+impl Hnsw {
+    fn new(config: HnswConfig) -> Self {
+        let max_neighbors = config.max_neighbors.max(2);
+
+        Self {
+            config: HnswConfig {
+                max_neighbors,
+                ..config
+            },
+            links: Vec::new(),
+            entry_point: None,
+            level_factor: 1. / (max_neighbors as f64).ln(),
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Link a newly stored slot into the graph
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - &IdentityIndex<K>
+    /// * `slot` - usize
+    fn insert<K>(&mut self, index: &IdentityIndex<K>, slot: usize) {
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(slot);
+            return;
+        };
+
+        let query = index.vector(slot);
+        let top_level = self.links[entry_point].len() - 1;
+        let mut entry = Candidate {
+            similarity: dot(query, index.vector(entry_point)),
+            slot: entry_point,
+        };
+
+        // Greedily descend the layers above the level of the new slot
+        for layer in (level + 1..=top_level).rev() {
+            entry = self.greedy_search(index, query, entry, layer);
+        }
+
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(top_level)).rev() {
+            let found =
+                self.search_layer(index, query, &entries, self.config.ef_construction, layer);
+            let neighbors = found
+                .iter()
+                .take(self.max_links(layer))
+                .map(|candidate| candidate.slot)
+                .collect::<Vec<_>>();
+
+            for &neighbor in neighbors.iter() {
+                self.links[neighbor][layer].push(slot);
+                self.prune(index, neighbor, layer);
+            }
+
+            self.links[slot][layer] = neighbors;
+            entries = found;
+        }
+
+        if level > top_level {
+            self.entry_point = Some(slot);
+        }
+    }
+
+    /// Return the top_k slots which are the most similar to the query
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - &IdentityIndex<K>
+    /// * `query` - &[f32]
+    /// * `top_k` - usize
+    fn search<K>(&self, index: &IdentityIndex<K>, query: &[f32], top_k: usize) -> Vec<Candidate> {
+        let Some(entry_point) = self.entry_point else {
+            return vec![];
+        };
+
+        let mut entry = Candidate {
+            similarity: dot(query, index.vector(entry_point)),
+            slot: entry_point,
+        };
+
+        for layer in (1..self.links[entry_point].len()).rev() {
+            entry = self.greedy_search(index, query, entry, layer);
+        }
+
+        // Removed slots still route the search but are never returned, hence more candidates are explored
+        let tombstones = index.keys.len() - index.slots.len();
+        let ef = self.config.ef_search.max(top_k) + tombstones.min(top_k);
+
+        self.search_layer(index, query, &[entry], ef, 0)
+            .into_iter()
+            .filter(|candidate| index.keys[candidate.slot].is_some())
+            .take(top_k)
+            .collect()
+    }
+
+    /// Move to the most similar neighbor until no neighbor is more similar than the current slot
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - &IdentityIndex<K>
+    /// * `query` - &[f32]
+    /// * `entry` - Candidate
+    /// * `layer` - usize
+    fn greedy_search<K>(
+        &self,
+        index: &IdentityIndex<K>,
+        query: &[f32],
+        mut entry: Candidate,
+        layer: usize,
+    ) -> Candidate {
+        loop {
+            let best = self.links[entry.slot][layer]
+                .iter()
+                .map(|&slot| Candidate {
+                    similarity: dot(query, index.vector(slot)),
+                    slot,
+                })
+                .max();
+
+            match best {
+                Some(best) if best.similarity > entry.similarity => entry = best,
+                _ => return entry,
+            }
+        }
+    }
+
+    /// Return the ef slots of the layer which are the most similar to the query, sorted from the most similar
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - &IdentityIndex<K>
+    /// * `query` - &[f32]
+    /// * `entries` - &[Candidate]
+    /// * `ef` - usize
+    /// * `layer` - usize
+    fn search_layer<K>(
+        &self,
+        index: &IdentityIndex<K>,
+        query: &[f32],
+        entries: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited = entries.iter().map(|c| c.slot).collect::<HashSet<_>>();
+        let mut candidates = entries.iter().copied().collect::<BinaryHeap<_>>();
+        let mut found = entries
+            .iter()
+            .copied()
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = found.peek().map(|Reverse(c)| c.similarity);
+            if found.len() >= ef && worst.is_some_and(|worst| candidate.similarity < worst) {
+                break;
+            }
+
+            for &slot in self.links[candidate.slot][layer].iter() {
+                if !visited.insert(slot) {
+                    continue;
+                }
+
+                let neighbor = Candidate {
+                    similarity: dot(query, index.vector(slot)),
+                    slot,
+                };
+
+                let worst = found.peek().map(|Reverse(c)| c.similarity);
+                if found.len() < ef || worst.is_some_and(|worst| neighbor.similarity > worst) {
+                    candidates.push(neighbor);
+                    found.push(Reverse(neighbor));
+
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(candidate)| candidate)
+            .collect()
+    }
+
+    /// Keep the most similar neighbors of the slot when it has more links than allowed on the layer
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - &IdentityIndex<K>
+    /// * `slot` - usize
+    /// * `layer` - usize
+    fn prune<K>(&mut self, index: &IdentityIndex<K>, slot: usize, layer: usize) {
+        let max_links = self.max_links(layer);
+        if self.links[slot][layer].len() <= max_links {
+            return;
+        }
+
+        let vector = index.vector(slot);
+        let mut neighbors = self.links[slot][layer]
+            .iter()
+            .map(|&neighbor| Candidate {
+                similarity: dot(vector, index.vector(neighbor)),
+                slot: neighbor,
+            })
+            .collect::<Vec<_>>();
+
+        neighbors.sort_unstable_by(|a, b| b.cmp(a));
+        self.links[slot][layer] = neighbors
+            .into_iter()
+            .take(max_links)
+            .map(|candidate| candidate.slot)
+            .collect();
+    }
+
+    /// Return the maximum number of links of a slot on the layer
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - usize
+    fn max_links(&self, layer: usize) -> usize {
+        match layer {
+            0 => self.config.max_neighbors * 2,
+            _ => self.config.max_neighbors,
+        }
+    }
+
+    /// Draw the top layer of a new slot from an exponentially decaying distribution
+    fn random_level(&mut self) -> usize {
+        // xorshift64* is good enough to spread the slots across the layers
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let random = self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+
+        let uniform = ((random >> 11) as f64 + 1.) / (1u64 << 53) as f64;
+        (-uniform.ln() * self.level_factor) as usize
+    }
+}
+
+/// Compute the dot product of two vectors of the same length. The products are accumulated in independent lanes
+/// so that the loop is vectorized.
+///
+/// # Arguments
+///
+/// * `a` - &[f32]
+/// * `b` - &[f32]
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut lanes = [0.; LANES];
+    let chunks_a = a.chunks_exact(LANES);
+    let chunks_b = b.chunks_exact(LANES);
+    let remainder = chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .map(|(x, y)| x * y)
+        .sum::<f32>();
+
+    for (x, y) in chunks_a.zip(chunks_b) {
+        for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+            *lane += x * y;
+        }
+    }
+
+    lanes.iter().sum::<f32>() + remainder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dot, HnswConfig, IdentityIndex};
+    use crate::embedding::FaceEmbedding;
+    use crate::similarity::SimilarityConverter;
+
+    // Deterministic pseudo random embeddings
+    fn embeddings(count: usize, dimension: usize) -> Vec<FaceEmbedding> {
+        let mut state = 42u64;
+        (0..count)
+            .map(|_| {
+                let data = (0..dimension)
+                    .map(|_| {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        ((state >> 40) as f32 / (1u64 << 24) as f32) - 0.5
+                    })
+                    .collect::<Vec<_>>();
+
+                FaceEmbedding::new(data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expect_index_to_be_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<IdentityIndex<String>>();
+    }
+
+    #[test]
+    fn expect_dot_to_handle_remainder() {
+        let a = (1..=11).map(|v| v as f32).collect::<Vec<_>>();
+        let expected = a.iter().map(|v| v * v).sum::<f32>();
+
+        assert_eq!(dot(&a, &a), expected);
+    }
+
+    #[test]
+    fn expect_brute_force_to_return_sorted_top_k() {
+        let mut index = IdentityIndex::new();
+        for (key, embedding) in embeddings(50, 16).iter().enumerate() {
+            index.insert(key, embedding).unwrap();
+        }
+
+        let probe = embeddings(50, 16).remove(7);
+        let matches = index.search(&probe, 5).unwrap();
+
+        assert_eq!(matches.len(), 5);
+        assert_eq!(matches[0].key, 7);
+        assert!((matches[0].cosine - 1.).abs() < 1e-5);
+        assert!(matches.windows(2).all(|m| m[0].cosine >= m[1].cosine));
+
+        assert!(index.remove(&7));
+        assert!(!index.remove(&7));
+        assert_ne!(index.search(&probe, 1).unwrap()[0].key, 7);
+        assert!(index.insert(100, &FaceEmbedding::new(vec![1.; 8])).is_err());
+    }
+
+    #[test]
+    fn expect_percentage_to_use_the_converter_of_the_index() {
+        let converter = SimilarityConverter {
+            threshold: 0.9,
+            ..Default::default()
+        };
+        let mut index = IdentityIndex::new().with_converter(converter);
+        let data = embeddings(2, 16);
+        index.insert(0, &data[0]).unwrap();
+        index.insert(1, &data[1]).unwrap();
+
+        let found = index.search(&data[1], 1).unwrap().remove(0);
+        assert_eq!(found.percentage, converter.percentage(found.cosine));
+        assert_ne!(
+            found.percentage,
+            SimilarityConverter::default().percentage(found.cosine)
+        );
+    }
+
+    #[test]
+    fn expect_top_k_to_be_bounded_by_the_entries() {
+        let mut exact = IdentityIndex::new();
+        let mut approximate = IdentityIndex::with_hnsw(HnswConfig {
+            brute_force_limit: 0,
+            ..Default::default()
+        });
+        for (key, embedding) in embeddings(20, 16).iter().enumerate() {
+            exact.insert(key, embedding).unwrap();
+            approximate.insert(key, embedding).unwrap();
+        }
+
+        let probe = embeddings(20, 16).remove(3);
+        assert_eq!(exact.search(&probe, usize::MAX).unwrap().len(), 20);
+        assert_eq!(approximate.search(&probe, usize::MAX).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn expect_hnsw_to_match_brute_force() {
+        let config = HnswConfig {
+            brute_force_limit: 0,
+            ..Default::default()
+        };
+
+        let mut exact = IdentityIndex::new();
+        let mut approximate = IdentityIndex::with_hnsw(config);
+        let data = embeddings(1_000, 32);
+        for (key, embedding) in data.iter().enumerate() {
+            exact.insert(key, embedding).unwrap();
+            approximate.insert(key, embedding).unwrap();
+        }
+
+        // Remove enough entries to trigger a compaction of the graph
+        for key in 0..600 {
+            exact.remove(&key);
+            approximate.remove(&key);
+        }
+
+        let mut recalled = 0;
+        for probe in data.iter().step_by(20) {
+            let expected = exact.search(probe, 10).unwrap();
+            let found = approximate.search(probe, 10).unwrap();
+
+            recalled += found
+                .iter()
+                .filter(|m| expected.iter().any(|e| e.key == m.key))
+                .count();
+        }
+
+        // 50 probes with 10 results each
+        assert!(recalled as f32 / 500. > 0.95);
+        assert_eq!(approximate.len(), 400);
+    }
+}
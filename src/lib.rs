@@ -21,45 +21,88 @@
 //!
 //! To perform the comparison baklava required you to downlaod a model from the InsightFace repository
 //! that can be found at this link: <https://github.com/HyperInspire/InspireFace?tab=readme-ov-file#resource-package-list>
+use autocxx::c_void;
 use autocxx::prelude::*;
-use autocxx::{c_long, c_void};
 use error::FFIError;
 use ffi_wrapper::{
-    baklava_create_image_bitmap_from_path, baklava_create_image_stream_from_bitmap,
-    baklava_create_session_optional, HFCreateFaceFeature, HFDetectMode, HFExecuteFaceTrack,
-    HFFaceBasicToken, HFFaceComparison, HFFaceFeature, HFFaceFeatureWithRefExtractTo, HFGetTokens,
-    HFLaunchInspireFace, HFMultipleFaceData, HFReleaseFaceFeature, HFReleaseImageBitmap,
-    HFReleaseImageStream, HFReleaseInspireFaceSession, HFRotation, HF_ENABLE_FACE_RECOGNITION,
-    HSUCCEED,
+    baklava_face_comparison, baklava_face_feature_copy_from, HFFaceComparison,
+    HFFaceFeatureWithRefExtractTo, HSUCCEED,
 };
+use handle::Feature;
+use pool::SessionPool;
 use std::sync::{Arc, Mutex};
-use std::{
-    ffi::CString,
-    mem::{self},
-    str::FromStr,
-    thread,
-};
-
+#[cfg(not(feature = "rayon"))]
+use std::thread;
+
+pub mod alignment;
+pub mod annotate;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod benchmark;
+pub mod calibration;
+pub mod embedding;
+pub mod enrollment;
 pub mod error;
+pub mod face;
 mod ffi_wrapper;
+pub mod gallery;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod handle;
+pub mod image;
+pub mod index;
+pub mod model;
+mod pool;
+pub mod runtime;
+pub mod similarity;
+pub mod template;
+pub mod tracking;
+
+pub use alignment::AlignedFace;
+pub use annotate::{annotate, FaceAnnotation};
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncInsightFace;
+pub use benchmark::{BenchmarkReport, FailedPair};
+pub use calibration::{Calibration, LabeledPair};
+pub use embedding::FaceEmbedding;
+pub use enrollment::{read_identities, Identity};
+pub use face::DetectionSession;
+pub use gallery::{Gallery, GalleryConfig, GalleryMatch};
+pub use image::FaceImage;
+pub use index::{IdentityIndex, IndexMatch};
+pub use model::ModelInfo;
+pub use runtime::Runtime;
+pub use similarity::SimilarityConverter;
+pub use template::FaceTemplate;
+pub use tracking::TrackingSession;
 
 // Constants
 const SUCCESS: i64 = HSUCCEED as i64;
-const OUTPUT_MAX: f64 = 1.0;
-const OUTPUT_MIN: f64 = 0.01;
-const MIDDLE_SCORE: f64 = 0.6;
-const STEEPNESS: f64 = 8.;
-const RECOMMENDED_COSINE_THRESHOLD: f64 = 0.48;
+// Number of faces followed by the tracking session when selecting the best frames of a clip
+const TRACKING_MAX_FACES: i32 = 5;
+// Number of faces returned by `detect_faces` unless configured otherwise
+const DETECTION_MAX_FACES: i32 = 20;
 
 /// InsightFace is a struct which handle the internal pointers to compare two faces and returns the cosine value
 pub struct InsightFace {
     session: *mut c_void,
-    src_features: Vec<HFFaceFeature>,
-    target_feature: HFFaceFeature,
+    src_features: Vec<Feature>,
+    target_feature: Option<Feature>,
     chunks: Option<usize>,
+    converter: SimilarityConverter,
+    // Default threshold of is_similar
+    threshold: f32,
+    // Idle sessions of `detect_faces`, created on demand
+    detectors: Mutex<Vec<DetectionSession>>,
+    max_detected_faces: i32,
+    // Declared after the features so that the sessions are released after them
+    pool: SessionPool,
+    // Declared last so that the SDK is terminated once everything else is released
+    runtime: Runtime,
 }
 
 /// SessionHandler is a wrapper around the session pointer in order to be able to be used in the context of multithreading
+#[derive(Clone, Copy)]
 struct SessionHandler {
     session: *mut c_void,
 }
@@ -77,14 +120,13 @@ pub enum Methodology {
 // No need for Sync to be implemented as it's already done by the Mutex.
 unsafe impl Send for InsightFace {}
 unsafe impl Send for SessionHandler {}
-unsafe impl Send for HFFaceFeature {}
 
 // Add Sync implementation for InsightFace should the use want to use it within a LazyLock or OnceLock
 unsafe impl Sync for InsightFace {}
 
 impl InsightFace {
-    /// Create a new InsightFace handler. It needs to be only call once as it build a model. Therefore it's recommended
-    /// to use it within an `Arc<Mutex<InsightFace>>` to ensure thread safety. Chunk size is used in order to split the image data into smaller chunks for processing.
+    /// Create a new InsightFace handler. The model is launched by the first handler and shared with the following ones,
+    /// see `Runtime`. It's recommended to use it within an `Arc<Mutex<InsightFace>>` to ensure thread safety. Chunk size is used in order to split the image data into smaller chunks for processing.
     ///
     /// # Arguments
     ///
@@ -104,42 +146,99 @@ impl InsightFace {
         model: S,
         chunk_size: Option<usize>,
     ) -> Result<Self, FFIError<'a>> {
-        let model = CString::new(model.as_ref()).map_err(|_| FFIError::ModelLoad)?;
-
-        // We only need to initialize the model once.
-        unsafe {
-            if HFLaunchInspireFace(model.as_ptr()).0 != SUCCESS {
-                return Err(FFIError::ModelLoad);
-            }
-        }
+        Self::with_session_pool(model, chunk_size, 1)
+    }
 
-        let session_ptr = unsafe {
-            let mut result = c_long(0);
-            let session_ptr = baklava_create_session_optional(
-                c_int(HF_ENABLE_FACE_RECOGNITION as i32),
-                HFDetectMode::HF_DETECT_MODE_ALWAYS_DETECT,
-                c_int(1),
-                c_int(-1),
-                c_int(-1),
-                &mut result,
-            );
-
-            if result.0 != SUCCESS {
-                return Err(FFIError::Session);
-            }
+    /// Create a new InsightFace handler backed by a pool of independent sessions. `prepare_images` processes up to
+    /// `pool_size` chunks in parallel, each of them with its own session. Every session holds its own copy of the
+    /// inference state, hence the memory usage grows with the pool size.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - S
+    /// * `chunk_size` - `Option<usize>`
+    /// * `pool_size` - usize
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::InsightFace;
+    ///
+    /// let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    /// let insight_face = InsightFace::with_session_pool("./Megatron", Some(2), threads).unwrap();
+    /// ```
+    pub fn with_session_pool<'a, S: AsRef<str>>(
+        model: S,
+        chunk_size: Option<usize>,
+        pool_size: usize,
+    ) -> Result<Self, FFIError<'a>> {
+        Self::with_runtime(&Runtime::launch(model)?, chunk_size, pool_size)
+    }
 
-            session_ptr
-        };
+    /// Create a new InsightFace handler over a runtime which has already been launched. The model is shared with the
+    /// other handlers of the runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `runtime` - &Runtime
+    /// * `chunk_size` - `Option<usize>`
+    /// * `pool_size` - usize
+    pub fn with_runtime<'a>(
+        runtime: &Runtime,
+        chunk_size: Option<usize>,
+        pool_size: usize,
+    ) -> Result<Self, FFIError<'a>> {
+        let pool = SessionPool::new(pool_size)?;
 
         Ok(Self {
-            session: session_ptr,
+            session: pool.primary().session,
             src_features: vec![],
-            target_feature: unsafe { mem::zeroed() },
+            target_feature: None,
             chunks: chunk_size,
+            converter: SimilarityConverter::query()?,
+            threshold: runtime.recommended_threshold(),
+            detectors: Mutex::new(vec![]),
+            max_detected_faces: DETECTION_MAX_FACES,
+            pool,
+            runtime: runtime.clone(),
         })
     }
 
-    /// Prepare a slice of a images to be compared toward the target
+    /// Return the runtime the handler has been created with
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Return the information of the model pack used by the handler
+    pub fn model_info(&self) -> Arc<ModelInfo> {
+        self.runtime.model_info()
+    }
+
+    /// Return the similarity converter used to compute the percentages of the handler
+    pub fn similarity_converter(&self) -> &SimilarityConverter {
+        &self.converter
+    }
+
+    /// Override the similarity converter used to compute the percentages of the handler. Its threshold becomes the
+    /// default threshold of `is_similar`. The other handlers and the SDK are not affected, see
+    /// `Runtime::update_similarity_converter` to change the converter of the SDK.
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - SimilarityConverter
+    pub fn set_similarity_converter(&mut self, converter: SimilarityConverter) -> &mut Self {
+        self.threshold = converter.threshold;
+        self.converter = converter;
+        self
+    }
+
+    /// Return the number of sessions used to prepare the images in parallel
+    pub fn pool_size(&self) -> usize {
+        self.pool.size()
+    }
+
+    /// Prepare a slice of a images to be compared toward the target. The images are split in chunks of `chunk_size`
+    /// images, each chunk being processed by its own thread with its own session of the pool.
+    /// With the `rayon` feature, the images are processed on the rayon thread pool of the caller instead.
     ///
     /// # Arguments
     ///
@@ -148,203 +247,317 @@ impl InsightFace {
         &mut self,
         sources: &[S],
     ) -> Result<&mut Self, FFIError<'a>> {
-        self.src_features = (0..sources.len())
-            .map(|_| unsafe { mem::zeroed() })
-            .collect();
-
-        let images = Arc::new(sources.to_vec());
-        let send_session = Arc::new(Mutex::new(SessionHandler {
-            session: self.session,
-        }));
+        // Release the features of the previous sources
+        self.src_features.clear();
+        let mut features = (0..sources.len()).map(|_| None).collect::<Vec<_>>();
 
         // By default we're going to spawn 1 thread which will do the task
         // We'll use the chunk_size as a reference to which the baklava library will spawn a set of threads.
-        let chunk_size = self.chunks.unwrap_or(1);
-
-        // Split the list of features in a set of chunks
-        let chunks_features = self.src_features.chunks_mut(chunk_size).collect::<Vec<_>>();
-        let chunks_len = chunks_features.len();
-
-        let chf = Arc::new(Mutex::new(chunks_features));
-
-        thread::scope(|s| -> Result<(), FFIError<'_>> {
-            let img_handle = images.clone();
-            let session_incr = send_session.clone();
-
-            // We avoid getting the feature here as the *mut HFFaceFeature is not Send.
-            for idx in 0..chunks_len {
-                let images_clone = img_handle.clone();
-                // Increase ref counting of the session
-                let session_incr = session_incr.clone();
-
-                // increase ref counting of chunks features
-                let chf = chf.clone();
-
-                s.spawn(move || -> Result<(), FFIError> {
-                    // Acquire the mutex
-                    let mut mutex = chf
-                        .lock()
-                        .map_err(|_| FFIError::IO("Unable to acquire lock"))?;
-
-                    let chunk = mutex
-                        .get_mut(idx)
-                        .ok_or(FFIError::IO("Unable to acquire lock"))?;
-
-                    for (iidx, feature) in chunk.iter_mut().enumerate() {
-                        let mut counter = iidx;
-                        if idx > 0 {
-                            // Increase the counter based on the current position and the chunk_size. This ensure that each thread get it's own photo to process.
-                            counter = idx * chunk_size + iidx;
+        let chunk_size = self.chunks.unwrap_or(1).max(1);
+
+        #[cfg(not(feature = "rayon"))]
+        InsightFace::prepare_chunks(&mut features, sources, &self.pool, chunk_size)?;
+        #[cfg(feature = "rayon")]
+        InsightFace::prepare_chunks_with_rayon(&mut features, sources, &self.pool, chunk_size)?;
+
+        // Every slot has been filled as any failure is returned above
+        self.src_features = features
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(FFIError::Feature)?;
+
+        Ok(self)
+    }
+
+    /// Prepare the features by spawning a thread per chunk of images
+    ///
+    /// # Arguments
+    ///
+    /// * `src_features` - `&mut [Option<Feature>]`
+    /// * `sources` - `&[S]`
+    /// * `pool` - &SessionPool
+    /// * `chunk_size` - usize
+    #[cfg(not(feature = "rayon"))]
+    fn prepare_chunks<'a, S: AsRef<str> + Sync>(
+        src_features: &mut [Option<Feature>],
+        sources: &[S],
+        pool: &SessionPool,
+        chunk_size: usize,
+    ) -> Result<(), FFIError<'a>> {
+        thread::scope(|s| -> Result<(), FFIError<'a>> {
+            // Each thread owns its slice of features and of images. Hence no lock is needed other than the session pool one.
+            let handles = src_features
+                .chunks_mut(chunk_size)
+                .zip(sources.chunks(chunk_size))
+                .map(|(features, images)| {
+                    s.spawn(move || -> Result<(), FFIError<'a>> {
+                        // Waits for another chunk to be done when the pool is exhausted.
+                        let session = pool.acquire()?;
+
+                        for (feature, image) in features.iter_mut().zip(images) {
+                            let prepared =
+                                InsightFace::prepare_image_for_comparison(image.as_ref(), &session)
+                                    .map_err(|_| FFIError::IO("Unable to prepare image"))?;
+
+                            *feature = Some(prepared);
                         }
 
-                        let img_path = images_clone
-                            .get(counter)
-                            .map(|s| CString::new(s.as_ref()))
-                            .ok_or(FFIError::MissingImage)?
-                            .map_err(|_| FFIError::MissingImage)?;
-
-                        InsightFace::prepare_image_for_comparison(
-                            feature,
-                            img_path,
-                            session_incr.clone(),
-                        )
-                        .map_err(|_| FFIError::IO("Unable to prepare image"))?;
-                    }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
 
-                    Ok(())
-                });
+            for handle in handles {
+                handle
+                    .join()
+                    .map_err(|_| FFIError::IO("A thread preparing the images panicked"))??;
             }
 
             Ok(())
-        })?;
+        })
+    }
 
-        Ok(self)
+    /// Prepare the features on the rayon thread pool of the caller. Each worker checks out a session once and reuses
+    /// it for all the images it processes. The chunk size is the minimum number of images processed by a worker.
+    /// Sizing the session pool with `rayon::current_num_threads()` avoids the workers waiting for a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_features` - `&mut [Option<Feature>]`
+    /// * `sources` - `&[S]`
+    /// * `pool` - &SessionPool
+    /// * `chunk_size` - usize
+    #[cfg(feature = "rayon")]
+    fn prepare_chunks_with_rayon<'a, S: AsRef<str> + Sync>(
+        src_features: &mut [Option<Feature>],
+        sources: &[S],
+        pool: &SessionPool,
+        chunk_size: usize,
+    ) -> Result<(), FFIError<'a>> {
+        use rayon::prelude::*;
+
+        src_features
+            .par_iter_mut()
+            .zip(sources.par_iter())
+            .with_min_len(chunk_size)
+            .try_for_each_init(
+                || pool.acquire(),
+                |session, (feature, image)| -> Result<(), FFIError<'a>> {
+                    let session = session.as_ref().map_err(|_| FFIError::Session)?;
+                    let prepared =
+                        InsightFace::prepare_image_for_comparison(image.as_ref(), session)
+                            .map_err(|_| FFIError::IO("Unable to prepare image"))?;
+
+                    *feature = Some(prepared);
+
+                    Ok(())
+                },
+            )
     }
 
-    /// Prepare the target image that will be compared against the sources images
+    /// Select the `top_k` best frames of the dominant face over a sequence of frames (e.g. extracted from a video clip)
+    /// and use their embeddings as the sources images. See `TrackingSession::select_best_frames` for how the frames are scored.
     ///
     /// # Arguments
     ///
-    /// * `target_img_path` - S
-    pub fn prepare_target_image<'a, S: AsRef<str>>(
+    /// * `frames` - `&[S]`
+    /// * `top_k` - usize
+    pub fn prepare_best_frames<'a, S: AsRef<str>>(
         &mut self,
-        target_img_path: S,
+        frames: &[S],
+        top_k: usize,
     ) -> Result<&mut Self, FFIError<'a>> {
-        let send_session = Arc::new(Mutex::new(SessionHandler {
-            session: self.session,
-        }));
+        let best_frames =
+            TrackingSession::new(self, TRACKING_MAX_FACES)?.select_best_frames(frames, top_k)?;
 
-        let img_path =
-            CString::from_str(target_img_path.as_ref()).map_err(|_| FFIError::Feature)?;
+        if best_frames.is_empty() {
+            return Err(FFIError::FaceTrack(
+                "Unable to find a face in any of the frames",
+            ));
+        }
 
-        InsightFace::prepare_image_for_comparison(
-            &mut self.target_feature,
-            img_path,
-            send_session,
-        )?;
+        let embeddings = best_frames
+            .into_iter()
+            .map(|frame| frame.embedding)
+            .collect::<Vec<_>>();
 
-        Ok(self)
+        self.prepare_embeddings(&embeddings)
     }
 
-    /// Prepare a set of images for comparison
+    /// Use a set of embeddings which have already been extracted as the sources images
     ///
     /// # Arguments
     ///
-    /// * `feature` - *mut HFFaceFeature
-    /// * `img_path` - CString
-    /// * `session_handler` - Arc<Mutex<SessionHandler>>
-    fn prepare_image_for_comparison<'a>(
-        feature: *mut HFFaceFeature,
-        img_path: CString,
-        session_handler: Arc<Mutex<SessionHandler>>,
-    ) -> Result<(), FFIError<'a>> {
-        unsafe {
-            // Initialize an HFMultipleFaceData structure in the way c++ would do
-            let mut multiple_face_data: HFMultipleFaceData = mem::zeroed();
+    /// * `sources` - `&[FaceEmbedding]`
+    pub fn prepare_embeddings<'a>(
+        &mut self,
+        sources: &[FaceEmbedding],
+    ) -> Result<&mut Self, FFIError<'a>> {
+        let model = self.model_info();
+        self.src_features = sources
+            .iter()
+            .map(|embedding| {
+                embedding.check_model(&model)?;
+                let mut feature = Feature::new()?;
+
+                let res = unsafe {
+                    baklava_face_feature_copy_from(
+                        feature.as_mut_ptr(),
+                        embedding.as_slice().as_ptr(),
+                        c_int(embedding.len() as i32),
+                    )
+                };
+
+                if res.0 != SUCCESS {
+                    return Err(FFIError::Feature);
+                }
 
-            if HFCreateFaceFeature(feature).0 != SUCCESS {
-                return Err(FFIError::Feature);
-            }
+                Ok(feature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-            // Create bitmap from the file path. This will be used for face analysis
-            let mut result = c_long(0);
-            let img_ptr =
-                baklava_create_image_bitmap_from_path(img_path.as_ptr(), c_int(3), &mut result);
+        Ok(self)
+    }
 
-            if result.0 != SUCCESS || img_ptr.is_null() {
-                return Err(FFIError::Bitmap(
-                    "image may not be the proper size or format",
-                ));
-            }
+    /// Prepare the target image that will be compared against the sources images
+    ///
+    /// # Arguments
+    ///
+    /// * `target_img_path` - S
+    pub fn prepare_target_image<'a, S: AsRef<str>>(
+        &mut self,
+        target_img_path: S,
+    ) -> Result<&mut Self, FFIError<'a>> {
+        // Release the feature of the previous target
+        self.target_feature = None;
+        self.target_feature = Some(InsightFace::prepare_image_for_comparison(
+            target_img_path.as_ref(),
+            &self.pool.primary(),
+        )?);
 
-            let mut result = c_long(0);
-            let stream_ptr = baklava_create_image_stream_from_bitmap(
-                img_ptr,
-                HFRotation::HF_CAMERA_ROTATION_0,
-                &mut result,
-            );
-
-            if result.0 != SUCCESS || stream_ptr.is_null() {
-                InsightFace::release_ptr(img_ptr, stream_ptr);
-                return Err(FFIError::Stream(
-                    "Unable to create stream issue with rotation",
-                ));
-            }
+        Ok(self)
+    }
 
-            let mutex = session_handler
-                .lock()
-                .map_err(|_| FFIError::Comparison("Unable to acquire the session handler lock"))?;
+    /// Extract the feature of the face of an image for comparison
+    ///
+    /// # Arguments
+    ///
+    /// * `img_path` - &str
+    /// * `session_handler` - &SessionHandler
+    fn prepare_image_for_comparison<'a>(
+        img_path: &str,
+        session_handler: &SessionHandler,
+    ) -> Result<Feature, FFIError<'a>> {
+        let mut feature = Feature::new()?;
 
-            if HFExecuteFaceTrack(mutex.session, stream_ptr, &mut multiple_face_data).0 != SUCCESS {
-                InsightFace::release_ptr(img_ptr, stream_ptr);
-                return Err(FFIError::FaceTrack(""));
-            }
+        face::execute_face_track(
+            session_handler.session,
+            img_path,
+            |session, stream_ptr, face_data| {
+                let single_face = face::read_tokens(face_data)?
+                    .first()
+                    .ok_or(FFIError::FaceTrack("Unable to get the processed feature"))?;
+
+                let res = unsafe {
+                    HFFaceFeatureWithRefExtractTo(
+                        session,
+                        stream_ptr,
+                        single_face as *const _ as *mut _,
+                        feature.as_mut_ptr(),
+                    )
+                };
+
+                if res.0 != SUCCESS {
+                    return Err(FFIError::FaceTrack(
+                        "Unable to extract feature from stream_ptr",
+                    ));
+                }
 
-            let tokens_slice = HFGetTokens(&mut multiple_face_data);
-            if tokens_slice.ptr.is_null() {
-                InsightFace::release_ptr(img_ptr, stream_ptr);
-                return Err(FFIError::FaceTrack(
-                    "Unable to construct list of tokens due to tokens slice being null",
-                ));
-            }
+                Ok(())
+            },
+        )?;
 
-            let tokens_ptr = tokens_slice.ptr as *mut HFFaceBasicToken;
-            let tokens = std::slice::from_raw_parts_mut(tokens_ptr, tokens_slice.len as usize);
+        Ok(feature)
+    }
 
-            let single_face = tokens.first_mut().ok_or_else(|| {
-                InsightFace::release_ptr(img_ptr, stream_ptr);
-                FFIError::FaceTrack("Unable to get the processed feature")
-            })?;
+    /// Compare the images and return the cosine similary which can range from 1 to -1
+    ///
+    /// # Arguments
+    ///
+    /// * `methodology` - Methodology
+    pub fn compare_images<'a>(&self, methodology: Methodology) -> Result<(f32, f64), FFIError<'a>> {
+        let target = self.target_feature.as_ref().ok_or(FFIError::Comparison(
+            "The target image has not been prepared",
+        ))?;
 
-            let res =
-                HFFaceFeatureWithRefExtractTo(mutex.session, stream_ptr, single_face, feature);
+        let mut cosine_result = Vec::new();
 
-            if res.0 != SUCCESS {
-                InsightFace::release_ptr(img_ptr, stream_ptr);
-                return Err(FFIError::FaceTrack(
-                    "Unable to extract feature from stream_ptr",
-                ));
+        for feature in self.src_features.iter() {
+            let mut res: f32 = 0.0;
+            unsafe {
+                let op_res = HFFaceComparison(feature.as_raw(), target.as_raw(), &mut res);
+                if op_res.0 != SUCCESS {
+                    return Err(FFIError::Comparison("Comparison fail"));
+                }
             }
 
-            // Clean unused memory
-            InsightFace::release_ptr(img_ptr, stream_ptr);
+            cosine_result.push(res);
         }
 
-        Ok(())
+        let cosine = Self::aggregate_cosines(cosine_result, methodology)?;
+
+        // Compute the percentage as well by reusing the formula used in in InspireFace SDK
+        Ok((cosine, self.converter.percentage(cosine)))
     }
 
-    /// Compare the images and return the cosine similary which can range from 1 to -1
+    /// Compare a target embedding against the sources embeddings. Unlike `compare_images` it does not rely on the
+    /// state of the handler, hence a shared handler can serve several comparisons concurrently.
     ///
     /// # Arguments
     ///
+    /// * `sources` - `&[FaceEmbedding]`
+    /// * `target` - &FaceEmbedding
     /// * `methodology` - Methodology
-    pub fn compare_images<'a>(&self, methodology: Methodology) -> Result<(f32, f64), FFIError<'a>> {
-        let mut cosine_result = Vec::new();
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{InsightFace, Methodology};
+    ///
+    /// let handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let sources = [handler.extract("./face1_test.png").unwrap()];
+    /// let target = handler.extract("./face2_test.png").unwrap();
+    ///
+    /// let (cosine, percentage) = handler.compare(&sources, &target, Methodology::Mean).unwrap();
+    /// ```
+    pub fn compare<'a>(
+        &self,
+        sources: &[FaceEmbedding],
+        target: &FaceEmbedding,
+        methodology: Methodology,
+    ) -> Result<(f32, f64), FFIError<'a>> {
+        if sources.is_empty() {
+            return Err(FFIError::Comparison(
+                "No source embedding to compare against",
+            ));
+        }
+
+        let model = self.model_info();
+        target.check_model(&model)?;
+
+        let mut cosine_result = Vec::with_capacity(sources.len());
+        for source in sources {
+            source.check_model(&model)?;
 
-        for feature in self.src_features.iter() {
             let mut res: f32 = 0.0;
             unsafe {
-                let op_res = HFFaceComparison(feature, &self.target_feature, &mut res);
+                // The buffers are only read by the SDK
+                let op_res = baklava_face_comparison(
+                    source.as_slice().as_ptr() as *mut f32,
+                    target.as_slice().as_ptr() as *mut f32,
+                    c_int(target.len() as i32),
+                    &mut res,
+                );
+
                 if op_res.0 != SUCCESS {
                     return Err(FFIError::Comparison("Comparison fail"));
                 }
@@ -353,6 +566,21 @@ impl InsightFace {
             cosine_result.push(res);
         }
 
+        let cosine = Self::aggregate_cosines(cosine_result, methodology)?;
+
+        Ok((cosine, self.converter.percentage(cosine)))
+    }
+
+    /// Aggregate the cosines computed against each source into a single cosine
+    ///
+    /// # Arguments
+    ///
+    /// * `cosine_result` - `Vec<f32>`
+    /// * `methodology` - Methodology
+    fn aggregate_cosines<'a>(
+        mut cosine_result: Vec<f32>,
+        methodology: Methodology,
+    ) -> Result<f32, FFIError<'a>> {
         // When the sample size is too small. We're unable to perform the median methodology. Hence better use the mean methodology in that case
         if cosine_result.len() == 2 && methodology == Methodology::Median {
             return Err(FFIError::Comparison(
@@ -362,22 +590,22 @@ impl InsightFace {
 
         let cosine = match methodology {
             Methodology::Mean => {
-                cosine_result.into_iter().fold(0., |acc, x| acc + x)
-                    / self.src_features.len() as f32
+                let len = cosine_result.len() as f32;
+                cosine_result.into_iter().fold(0., |acc, x| acc + x) / len
             }
             Methodology::Median => {
                 // Sort the cosine result in ASC
                 cosine_result.sort_unstable_by(|a, b| a.total_cmp(b));
                 let mid = cosine_result.len() / 2;
 
-                match cosine_result.len() % 2 == 0 {
+                match cosine_result.len().is_multiple_of(2) {
                     true => {
                         let low = cosine_result
                             .get(mid - 1)
                             .ok_or(FFIError::Comparison("Unable to get the low mid"))?;
 
                         let high = cosine_result
-                            .get(mid + 1)
+                            .get(mid)
                             .ok_or(FFIError::Comparison("Unable to get the high mid"))?;
 
                         (*low + *high) / 2.
@@ -390,60 +618,18 @@ impl InsightFace {
             }
         };
 
-        // Compute the percentage as well by reusing the formula used in in InspireFace SDK
-        Ok((cosine, Self::compute_percentage(cosine)))
+        Ok(cosine)
     }
 
-    /// Return whether the two faces are similar based on the cosine
+    /// Return whether the two faces are similar based on the cosine. The threshold defaults to the one recommended
+    /// by the loaded model pack, or to the threshold of the converter set with `set_similarity_converter`.
     ///
     /// # Arguments
     ///
     /// * `cosine` - f32
     /// * `threshold` - `Option<f64>`
-    pub fn is_similar(cosine: f32, threshold: Option<f64>) -> bool {
-        cosine as f64 >= threshold.unwrap_or(RECOMMENDED_COSINE_THRESHOLD)
-    }
-
-    /// Compute the percentage of similarity from the computed cosine. Based on the inspireface SDK formula
-    ///
-    /// # Arguments
-    ///
-    /// * `cosine` - f32
-    fn compute_percentage(cosine: f32) -> f64 {
-        let bias = -f64::ln((OUTPUT_MAX - MIDDLE_SCORE) / (MIDDLE_SCORE - OUTPUT_MIN));
-        let output_scale = OUTPUT_MAX - OUTPUT_MIN;
-
-        let shifted_input = STEEPNESS * (cosine as f64 - RECOMMENDED_COSINE_THRESHOLD);
-        let sigmoid = 1. / (1. + f64::exp(-shifted_input - bias));
-
-        sigmoid * output_scale + OUTPUT_MIN
-    }
-
-    /// Release pointers which are used for the image & stream
-    ///
-    /// # Arguments
-    ///
-    /// * `img_ptr` - c_void
-    /// * `stream_ptr` - c_void
-    fn release_ptr(img_ptr: *mut c_void, stream_ptr: *mut c_void) {
-        unsafe {
-            HFReleaseImageBitmap(img_ptr);
-            HFReleaseImageStream(stream_ptr);
-        }
-    }
-}
-
-impl Drop for InsightFace {
-    fn drop(&mut self) {
-        unsafe {
-            HFReleaseInspireFaceSession(self.session);
-
-            // Release all the features
-            for feature in self.src_features.iter_mut() {
-                // We can safely release the feature as it was created by us using mem::zeroed()
-                HFReleaseFaceFeature(feature);
-            }
-        }
+    pub fn is_similar(&self, cosine: f32, threshold: Option<f64>) -> bool {
+        cosine as f64 >= threshold.unwrap_or(self.threshold as f64)
     }
 }
 
@@ -530,4 +716,375 @@ mod tests {
             "Unable to compare image due to: Sample size is too small. You should consider to use the mean methodology instead"
         );
     }
+
+    #[test]
+    fn expect_to_prepare_images_again_after_a_failure() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        assert!(model
+            .prepare_images(&["./face1_test.png", "./missing.png"])
+            .is_err());
+
+        let (cos, _) = model
+            .prepare_images(&["./face1_test.png", "./face2_test.png"])
+            .unwrap()
+            .prepare_target_image("./face1_test.png")
+            .unwrap()
+            .compare_images(Methodology::Mean)
+            .unwrap();
+
+        assert!(cos > 0.6);
+    }
+
+    #[test]
+    fn expect_to_compare_image_with_session_pool() {
+        // The pool is created over the runtime of the shared client, hence the SDK is never launched twice
+        let runtime = INSIGHT_FACE_CLIENT.lock().unwrap().runtime().clone();
+
+        let mut model = runtime.handler(Some(1), 2).unwrap();
+        assert_eq!(model.pool_size(), 2);
+
+        let (cos, percentage) = model
+            .prepare_images(&["./face1_test.png", "./face1_test.png", "./face1_test.png"])
+            .unwrap()
+            .prepare_target_image("./face1_test.png")
+            .unwrap()
+            .compare_images(Methodology::Mean)
+            .unwrap();
+
+        assert!(cos > 0.6);
+        assert!(percentage > 0.6);
+    }
+
+    #[test]
+    fn expect_runtime_to_be_shared_across_handlers() {
+        // Make sure the model has been downloaded
+        drop(INSIGHT_FACE_CLIENT.lock().unwrap());
+
+        let runtime = crate::Runtime::launch("./Megatron").unwrap();
+        assert_eq!(runtime.model(), "./Megatron");
+
+        // The SDK is already launched with the model, hence it's not launched again
+        let mut handler = runtime.handler(None, 1).unwrap();
+        let embedding = handler.extract_embedding("./face1_test.png").unwrap();
+        assert!(!embedding.is_empty());
+
+        assert!(crate::Runtime::launch("./Pikachu").is_err());
+        assert!(runtime.reload("./Megatron").is_err());
+    }
+
+    #[test]
+    fn expect_embeddings_to_be_tagged_with_the_model() {
+        let handler = INSIGHT_FACE_CLIENT.lock().unwrap();
+        let info = handler.model_info();
+        assert_eq!(info.pack, "Megatron");
+        assert!(info.feature_length > 0);
+
+        let embedding = handler.extract("./face1_test.png").unwrap();
+        assert_eq!(embedding.len(), info.feature_length);
+        assert_eq!(embedding.model(), Some(info.as_ref()));
+    }
+
+    #[test]
+    fn expect_similarity_converter_to_be_overridden_per_handler() {
+        // Make sure the model has been downloaded
+        drop(INSIGHT_FACE_CLIENT.lock().unwrap());
+
+        let mut handler = InsightFace::new("./Megatron", None).unwrap();
+        let sources = [handler.extract("./face1_test.png").unwrap()];
+        let target = handler.extract("./face2_test.png").unwrap();
+
+        let (cosine, percentage) = handler
+            .compare(&sources, &target, Methodology::Mean)
+            .unwrap();
+        assert!(handler.is_similar(cosine, None));
+
+        let strict = crate::SimilarityConverter {
+            threshold: 0.99,
+            ..*handler.similarity_converter()
+        };
+        let (_, strict_percentage) = handler
+            .set_similarity_converter(strict)
+            .compare(&sources, &target, Methodology::Mean)
+            .unwrap();
+
+        assert!(strict_percentage < percentage);
+        assert!(!handler.is_similar(cosine, None));
+        assert_ne!(
+            handler.runtime().similarity_converter().unwrap(),
+            *handler.similarity_converter()
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn expect_to_compare_image_asynchronously() {
+        let shared = INSIGHT_FACE_CLIENT
+            .lock()
+            .unwrap()
+            .runtime()
+            .handler(None, 2);
+        let handler = crate::AsyncInsightFace::new(shared.unwrap(), 2);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let (cos, percentage) = runtime
+            .block_on(handler.compare(
+                vec!["./face1_test.png".to_string()],
+                "./face1_test.png".to_string(),
+                Methodology::Mean,
+            ))
+            .unwrap();
+
+        assert!(cos > 0.6);
+        assert!(percentage > 0.6);
+        assert_eq!(handler.available_slots(), 2);
+    }
+
+    #[test]
+    fn expect_to_compare_embeddings_without_mutating_the_handler() {
+        let model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let sources = [
+            model.extract("./face1_test.png").unwrap(),
+            model.extract("./face2_test.png").unwrap(),
+        ];
+        let target = model.extract("./face1_test.png").unwrap();
+
+        let (cos, percentage) = model
+            .compare(&sources[..1], &target, Methodology::Mean)
+            .unwrap();
+        assert!(cos > 0.6);
+        assert!(percentage > 0.6);
+
+        let (cos, _) = model.compare(&sources, &target, Methodology::Mean).unwrap();
+        assert!(cos > 0.6);
+        assert!(model.compare(&[], &target, Methodology::Mean).is_err());
+    }
+
+    #[test]
+    fn expect_to_compare_best_frames() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let (cos, percentage) = model
+            .prepare_best_frames(
+                &["./face1_test.png", "./face1_test.png", "./face1_test.png"],
+                2,
+            )
+            .unwrap()
+            .prepare_target_image("./face1_test.png")
+            .unwrap()
+            .compare_images(Methodology::Mean)
+            .unwrap();
+
+        assert!(cos > 0.6);
+        assert!(percentage > 0.6);
+    }
+
+    #[test]
+    fn expect_to_compare_template() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let template = model
+            .build_template(&["./face1_test.png", "./face1_test.png"])
+            .unwrap();
+
+        let (cos, percentage) = model
+            .prepare_template(&template)
+            .unwrap()
+            .prepare_target_image("./face1_test.png")
+            .unwrap()
+            .compare_images(Methodology::Mean)
+            .unwrap();
+
+        let probe = model.extract_embedding("./face1_test.png").unwrap();
+        let (template_cos, _) = template
+            .compare(&probe, model.similarity_converter())
+            .unwrap();
+
+        assert!(cos > 0.6);
+        assert!(percentage > 0.6);
+        assert!((cos - template_cos).abs() < 1e-3);
+    }
+
+    #[test]
+    fn expect_to_align_face() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let aligned = model.align_face("./face1_test.png").unwrap();
+
+        assert_eq!(aligned.image.width(), 112);
+        assert_eq!(aligned.image.height(), 112);
+        assert!(aligned.face.rect.area() > 0);
+    }
+
+    #[test]
+    fn expect_to_compare_aligned_face() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let aligned = model.align_face("./face1_test.png").unwrap();
+        let embedding = model.extract_aligned_face(&aligned.image).unwrap();
+
+        let (cos, _) = model
+            .prepare_embeddings(&[embedding])
+            .unwrap()
+            .prepare_target_image("./face1_test.png")
+            .unwrap()
+            .compare_images(Methodology::Mean)
+            .unwrap();
+
+        assert!(cos > 0.6);
+    }
+
+    #[test]
+    fn expect_to_annotate_image() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let faces = model.detect_faces("./face1_test.png").unwrap();
+        assert_eq!(faces.len(), 1);
+
+        let image = crate::FaceImage::from_path("./face1_test.png").unwrap();
+        let annotated = crate::annotate(
+            &image,
+            &[crate::FaceAnnotation {
+                face: faces[0],
+                cosine: Some(0.82),
+                selected: true,
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(annotated.width(), image.width());
+        assert_ne!(annotated.as_bytes(), image.as_bytes());
+    }
+
+    /// Put two images of the same number of channels side by side, the shortest one being padded with black pixels
+    fn side_by_side(left: &crate::FaceImage, right: &crate::FaceImage) -> crate::FaceImage {
+        let channels = left.channels() as usize;
+        let height = left.height().max(right.height());
+        let row = |image: &crate::FaceImage, y: i32| {
+            let len = image.width() as usize * channels;
+            match y < image.height() {
+                true => image.as_bytes()[y as usize * len..(y as usize + 1) * len].to_vec(),
+                false => vec![0; len],
+            }
+        };
+
+        let data = (0..height)
+            .flat_map(|y| [row(left, y), row(right, y)].concat())
+            .collect();
+
+        crate::FaceImage::new(left.width() + right.width(), height, left.channels(), data).unwrap()
+    }
+
+    #[test]
+    fn expect_every_face_of_an_image_to_be_detected() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+        let group = side_by_side(
+            &crate::FaceImage::from_path("./face1_test.png").unwrap(),
+            &crate::FaceImage::from_path("./face2_test.png").unwrap(),
+        );
+
+        let faces = model.detect_faces_in_image(&group).unwrap();
+        assert_eq!(faces.len(), 2);
+
+        let selected = model.detect_selected_face(&group).unwrap().unwrap();
+        let (x, y) = (
+            selected.rect.x + selected.rect.width / 2,
+            selected.rect.y + selected.rect.height / 2,
+        );
+        assert!(faces.iter().any(|face| {
+            (face.rect.x..face.rect.x + face.rect.width).contains(&x)
+                && (face.rect.y..face.rect.y + face.rect.height).contains(&y)
+        }));
+
+        model.set_max_detected_faces(1);
+        let faces = model.detect_faces_in_image(&group);
+        model.set_max_detected_faces(20);
+        assert_eq!(faces.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn expect_to_annotate_every_face() {
+        let model = INSIGHT_FACE_CLIENT.lock().unwrap();
+        let group = side_by_side(
+            &crate::FaceImage::from_path("./face1_test.png").unwrap(),
+            &crate::FaceImage::from_path("./face2_test.png").unwrap(),
+        );
+
+        let annotated = model.annotate_faces(&group).unwrap();
+        assert_eq!(annotated.width(), group.width());
+        assert_ne!(annotated.as_bytes(), group.as_bytes());
+    }
+
+    #[test]
+    fn expect_median_of_an_even_number_of_cosines_to_average_the_middle_ones() {
+        let median = InsightFace::aggregate_cosines(vec![0.4, 0.1, 0.3, 0.2], Methodology::Median);
+        assert!((median.unwrap() - 0.25).abs() < f32::EPSILON);
+
+        let median = InsightFace::aggregate_cosines(vec![0.9, 0.1, 0.5], Methodology::Median);
+        assert_eq!(median.unwrap(), 0.5);
+    }
+
+    #[test]
+    fn expect_in_memory_image_to_match_the_file() {
+        let model = INSIGHT_FACE_CLIENT.lock().unwrap();
+
+        let image = crate::FaceImage::decode(&std::fs::read("./face1_test.png").unwrap()).unwrap();
+        assert_eq!(model.detect_faces_in_image(&image).unwrap().len(), 1);
+
+        let (cos, _) = model
+            .compare(
+                &[model.extract_image(&image).unwrap()],
+                &model.extract("./face1_test.png").unwrap(),
+                Methodology::Mean,
+            )
+            .unwrap();
+
+        assert!(cos > 0.99);
+    }
+
+    #[test]
+    fn expect_gallery_to_identify_face() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+        let mut gallery = crate::Gallery::new(&model, None).unwrap();
+
+        gallery.enroll(&mut model, 1, "./face1_test.png").unwrap();
+        gallery.enroll(&mut model, 2, "./face2_test.png").unwrap();
+
+        let matches = gallery.identify(&mut model, "./face1_test.png", 2).unwrap();
+        assert_eq!(matches.first().map(|m| m.id), Some(1));
+
+        gallery.remove(1).unwrap();
+        let matches = gallery.identify(&mut model, "./face1_test.png", 2).unwrap();
+        assert!(matches.iter().all(|m| m.id != 1));
+    }
+
+    #[test]
+    fn expect_gallery_to_be_persisted() {
+        let mut model = INSIGHT_FACE_CLIENT.lock().unwrap();
+        let path = std::env::temp_dir().join("baklava_gallery_test.db");
+        let _ = std::fs::remove_file(&path);
+
+        let config = crate::GalleryConfig {
+            persistence_path: path.to_str().map(String::from),
+            primary_key: crate::gallery::PrimaryKeyMode::AutoIncrement,
+            ..Default::default()
+        };
+
+        let id = {
+            let mut gallery = crate::Gallery::with_config(&model, config.clone()).unwrap();
+            let id = gallery.enroll(&mut model, 0, "./face1_test.png").unwrap();
+            assert_eq!(gallery.len().unwrap(), 1);
+
+            id
+        };
+
+        let mut gallery = crate::Gallery::with_config(&model, config).unwrap();
+        assert_eq!(gallery.ids().unwrap(), vec![id]);
+
+        let matches = gallery.identify(&mut model, "./face1_test.png", 1).unwrap();
+        assert_eq!(matches.first().map(|m| m.id), Some(id));
+    }
 }
@@ -0,0 +1,210 @@
+use crate::embedding;
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    baklava_query_extended_information, HFInspireFaceVersion, HFQueryInspireFaceVersion,
+};
+use crate::SUCCESS;
+use autocxx::prelude::*;
+use std::ffi::{c_char, CStr};
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+// Parameters of the 64 bits FNV-1a hash used as the digest of the model packs
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// SdkVersion is the version of the InspireFace library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SdkVersion {
+    pub major: i32,
+    pub minor: i32,
+    pub patch: i32,
+}
+
+impl fmt::Display for SdkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// ModelInfo describes the model pack loaded by the SDK. Every embedding extracted by a handler is tagged with it,
+/// embeddings coming from different packs can't be compared.
+///
+/// Two packs are the same when their content has the same digest and they extract embeddings of the same dimension.
+/// The name of the pack is only informative, a renamed pack is still the same pack.
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub version: SdkVersion,
+    /// Name of the model pack e.g. Megatron or Pikachu, which is the file name of its path
+    pub pack: String,
+    /// Digest of the content of the model pack
+    pub digest: u64,
+    /// Extended information reported by the SDK
+    pub information: String,
+    /// Dimension of the embeddings extracted with the model pack
+    pub feature_length: usize,
+}
+
+impl ModelInfo {
+    /// Query the information of the model pack which has been launched
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - &str
+    pub(crate) fn query<'a>(model: &str) -> Result<Self, FFIError<'a>> {
+        let mut version = HFInspireFaceVersion {
+            major: 0,
+            minor: 0,
+            patch: 0,
+        };
+        let mut information = [0 as c_char; 256];
+
+        unsafe {
+            if HFQueryInspireFaceVersion(&mut version).0 != SUCCESS {
+                return Err(FFIError::Model("unable to query the SDK version"));
+            }
+
+            if baklava_query_extended_information(
+                information.as_mut_ptr(),
+                c_int(information.len() as i32),
+            )
+            .0 != SUCCESS
+            {
+                return Err(FFIError::Model("unable to query the extended information"));
+            }
+        }
+
+        // The information is always nul terminated by the shim
+        let information = unsafe { CStr::from_ptr(information.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+
+        Ok(Self {
+            version: SdkVersion {
+                major: version.major,
+                minor: version.minor,
+                patch: version.patch,
+            },
+            pack: pack_name(model),
+            digest: pack_digest(model)?,
+            information,
+            feature_length: embedding::feature_length()?,
+        })
+    }
+}
+
+impl PartialEq for ModelInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest == other.digest && self.feature_length == other.feature_length
+    }
+}
+
+impl Eq for ModelInfo {}
+
+impl Hash for ModelInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digest.hash(state);
+        self.feature_length.hash(state);
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (InspireFace {}, {} dimensions)",
+            self.pack, self.version, self.feature_length
+        )
+    }
+}
+
+/// Return the name of the model pack out of its path
+///
+/// # Arguments
+///
+/// * `model` - &str
+fn pack_name(model: &str) -> String {
+    Path::new(model)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| model.to_string())
+}
+
+/// Return the digest of the content of the model pack. It's a 64 bits FNV-1a hash, which tells packs apart but isn't
+/// meant to resist tampering.
+///
+/// # Arguments
+///
+/// * `model` - &str
+fn pack_digest<'a>(model: &str) -> Result<u64, FFIError<'a>> {
+    let file = File::open(model).map_err(|_| FFIError::Model("unable to read the model pack"))?;
+    let mut reader = BufReader::new(file);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut digest = FNV_OFFSET_BASIS;
+
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|_| FFIError::Model("unable to read the model pack"))?;
+        if read == 0 {
+            return Ok(digest);
+        }
+
+        for byte in &buffer[..read] {
+            digest = (digest ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn expect_pack_name_to_be_extracted_from_the_path() {
+        assert_eq!(pack_name("./Megatron"), "Megatron");
+        assert_eq!(pack_name("/opt/models/Pikachu/"), "Pikachu");
+        assert_eq!(pack_name("Gundam_RV1106"), "Gundam_RV1106");
+    }
+
+    #[test]
+    fn expect_pack_digest_to_depend_on_the_content_only() {
+        let root = env::temp_dir().join(format!("baklava-packs-{}", process::id()));
+        for (dir, content) in [("a", "first"), ("b", "second"), ("c", "first")] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("Megatron"), content).unwrap();
+        }
+        fs::write(root.join("Renamed"), "first").unwrap();
+
+        let digest = |path: &str| pack_digest(root.join(path).to_str().unwrap()).unwrap();
+        let first = digest("a/Megatron");
+        assert_ne!(first, digest("b/Megatron"));
+        assert_eq!(first, digest("c/Megatron"));
+        assert_eq!(first, digest("Renamed"));
+        assert!(pack_digest(root.join("missing").to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn expect_model_info_to_be_compared_by_digest() {
+        let info = |pack: &str, digest: u64| ModelInfo {
+            version: SdkVersion {
+                major: 1,
+                minor: 2,
+                patch: 0,
+            },
+            pack: pack.to_string(),
+            digest,
+            information: String::new(),
+            feature_length: 512,
+        };
+
+        assert_eq!(info("Megatron", 1), info("Renamed", 1));
+        assert_ne!(info("Megatron", 1), info("Megatron", 2));
+    }
+}
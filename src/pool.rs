@@ -0,0 +1,111 @@
+use crate::error::FFIError;
+use crate::ffi_wrapper::{HFDetectMode, HF_ENABLE_FACE_RECOGNITION};
+use crate::handle::Session;
+use crate::SessionHandler;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex};
+
+/// SessionPool is a set of independent sessions. A session can only be used by one thread at a time, hence each
+/// worker checks out its own session so that the face tracking and the feature extraction run in parallel.
+pub(crate) struct SessionPool {
+    idle: Mutex<Vec<SessionHandler>>,
+    released: Condvar,
+    sessions: Vec<Session>,
+}
+
+// A session is only handed out to a single thread at a time through the idle list.
+unsafe impl Sync for SessionPool {}
+
+impl SessionPool {
+    /// Create a pool of sessions. The model needs to be launched beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - usize
+    pub(crate) fn new<'a>(size: usize) -> Result<Self, FFIError<'a>> {
+        if size == 0 {
+            return Err(FFIError::Session);
+        }
+
+        // The sessions created so far are released if one of them can't be created
+        let sessions = (0..size)
+            .map(|_| {
+                Session::new(
+                    HF_ENABLE_FACE_RECOGNITION,
+                    HFDetectMode::HF_DETECT_MODE_ALWAYS_DETECT,
+                    1,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let idle = sessions
+            .iter()
+            .map(|session| SessionHandler {
+                session: session.as_ptr(),
+            })
+            .collect();
+
+        Ok(Self {
+            idle: Mutex::new(idle),
+            released: Condvar::new(),
+            sessions,
+        })
+    }
+
+    /// Return the number of sessions of the pool
+    pub(crate) fn size(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Return the session which is used by the methods of the handler which are not run in parallel
+    pub(crate) fn primary(&self) -> SessionHandler {
+        SessionHandler {
+            session: self.sessions[0].as_ptr(),
+        }
+    }
+
+    /// Check out a session. Wait for a session to be released when all of them are in use.
+    pub(crate) fn acquire<'a>(&self) -> Result<PooledSession<'_>, FFIError<'a>> {
+        let mut idle = self
+            .idle
+            .lock()
+            .map_err(|_| FFIError::IO("Unable to acquire the session pool lock"))?;
+
+        loop {
+            if let Some(handler) = idle.pop() {
+                return Ok(PooledSession {
+                    pool: self,
+                    handler,
+                });
+            }
+
+            idle = self
+                .released
+                .wait(idle)
+                .map_err(|_| FFIError::IO("Unable to acquire the session pool lock"))?;
+        }
+    }
+}
+
+/// PooledSession is a session checked out of the pool. It goes back to the pool once dropped.
+pub(crate) struct PooledSession<'p> {
+    pool: &'p SessionPool,
+    handler: SessionHandler,
+}
+
+impl Deref for PooledSession<'_> {
+    type Target = SessionHandler;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handler
+    }
+}
+
+impl Drop for PooledSession<'_> {
+    fn drop(&mut self) {
+        // A poisoned lock only means another worker panicked, the list of idle sessions is still valid.
+        let mut idle = self.pool.idle.lock().unwrap_or_else(|err| err.into_inner());
+        idle.push(self.handler);
+        self.pool.released.notify_one();
+    }
+}
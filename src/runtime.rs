@@ -0,0 +1,275 @@
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    HFLaunchInspireFace, HFQueryInspireFaceLaunchStatus, HFReloadInspireFace,
+    HFTerminateInspireFace,
+};
+use crate::model::ModelInfo;
+use crate::similarity::{self, SimilarityConverter};
+use crate::{InsightFace, SUCCESS};
+use autocxx::prelude::*;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex, Weak};
+
+// The SDK is global to the process. The runtime which currently owns it is tracked here.
+static RUNTIME: Mutex<Launched> = Mutex::new(Launched {
+    runtime: Weak::new(),
+    model: None,
+});
+
+/// Launched is the runtime which owns the SDK along with the path of the model pack the SDK has been launched with.
+/// The path outlives the runtime while it's dropping, until the SDK is terminated.
+struct Launched {
+    runtime: Weak<RuntimeInner>,
+    model: Option<String>,
+}
+
+/// Runtime is a handle over the InspireFace SDK which is global to the process. The SDK is launched by the first
+/// runtime and terminated once the last handle is dropped. Every handler, tracking session and gallery keeps a handle,
+/// hence the SDK can't be terminated while they are in use.
+#[derive(Clone)]
+pub struct Runtime {
+    inner: Arc<RuntimeInner>,
+}
+
+struct RuntimeInner {
+    model: Mutex<LoadedModel>,
+}
+
+/// LoadedModel is the model pack currently loaded by the SDK
+struct LoadedModel {
+    path: String,
+    info: Arc<ModelInfo>,
+    threshold: f32,
+}
+
+impl LoadedModel {
+    /// Query the model pack which has been loaded from the path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - &str
+    fn query<'a>(path: &str) -> Result<Self, FFIError<'a>> {
+        Ok(Self {
+            path: path.to_string(),
+            info: Arc::new(ModelInfo::query(path)?),
+            threshold: similarity::recommended_threshold()?,
+        })
+    }
+}
+
+impl Runtime {
+    /// Launch the SDK with the given model pack or return a handle over the runtime already launched with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - S
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::Runtime;
+    ///
+    /// let runtime = Runtime::launch("./Megatron").unwrap();
+    /// let handler = runtime.handler(None, 2).unwrap();
+    /// ```
+    pub fn launch<'a, S: AsRef<str>>(model: S) -> Result<Self, FFIError<'a>> {
+        let mut current = RUNTIME
+            .lock()
+            .map_err(|_| FFIError::Runtime("the runtime lock is poisoned"))?;
+
+        if let Some(inner) = current.runtime.upgrade() {
+            let runtime = Self { inner };
+            if runtime.model() != model.as_ref() {
+                return Err(FFIError::Runtime(
+                    "the SDK is already launched with another model, use reload instead",
+                ));
+            }
+
+            return Ok(runtime);
+        }
+
+        // The previous runtime may be dropping, in which case it leaves the launched SDK to this one as long as it has
+        // been launched with the same model pack.
+        let launched = !is_launched()?;
+        if !launched && current.model.as_deref() != Some(model.as_ref()) {
+            return Err(FFIError::Runtime(
+                "the SDK is still launched with another model",
+            ));
+        }
+
+        if launched {
+            let path = CString::new(model.as_ref()).map_err(|_| FFIError::ModelLoad)?;
+            unsafe {
+                if HFLaunchInspireFace(path.as_ptr()).0 != SUCCESS {
+                    return Err(FFIError::ModelLoad);
+                }
+            }
+        }
+
+        let loaded = match LoadedModel::query(model.as_ref()) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                // Nothing owns the SDK yet
+                if launched {
+                    unsafe {
+                        HFTerminateInspireFace();
+                    }
+                }
+
+                return Err(err);
+            }
+        };
+
+        let inner = Arc::new(RuntimeInner {
+            model: Mutex::new(loaded),
+        });
+        current.runtime = Arc::downgrade(&inner);
+        current.model = Some(model.as_ref().to_string());
+
+        Ok(Self { inner })
+    }
+
+    /// Return the path of the model pack currently loaded
+    pub fn model(&self) -> String {
+        self.loaded(|model| model.path.clone())
+    }
+
+    /// Return the information of the model pack currently loaded
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::Runtime;
+    ///
+    /// let runtime = Runtime::launch("./Megatron").unwrap();
+    /// let info = runtime.model_info();
+    /// println!("{} embeddings of {} dimensions", info.pack, info.feature_length);
+    /// ```
+    pub fn model_info(&self) -> Arc<ModelInfo> {
+        self.loaded(|model| model.info.clone())
+    }
+
+    /// Return the cosine threshold recommended by the model pack currently loaded
+    pub fn recommended_threshold(&self) -> f32 {
+        self.loaded(|model| model.threshold)
+    }
+
+    /// Return the similarity converter configured in the SDK. It's loaded from the model pack unless it has been
+    /// updated since.
+    pub fn similarity_converter<'a>(&self) -> Result<SimilarityConverter, FFIError<'a>> {
+        SimilarityConverter::query()
+    }
+
+    /// Replace the similarity converter of the SDK. Only the handlers created afterward use it, the existing ones keep
+    /// the converter they have been created with.
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - &SimilarityConverter
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::Runtime;
+    ///
+    /// let runtime = Runtime::launch("./Megatron").unwrap();
+    /// let mut converter = runtime.similarity_converter().unwrap();
+    /// converter.threshold = 0.42;
+    /// runtime.update_similarity_converter(&converter).unwrap();
+    /// ```
+    pub fn update_similarity_converter<'a>(
+        &self,
+        converter: &SimilarityConverter,
+    ) -> Result<(), FFIError<'a>> {
+        converter.update()
+    }
+
+    /// Swap the model pack loaded by the SDK. Reloading releases all the resources of the SDK, hence it's only
+    /// allowed when no handler, tracking session or gallery is alive. They need to be created again afterward.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - S
+    pub fn reload<'a, S: AsRef<str>>(&self, model: S) -> Result<(), FFIError<'a>> {
+        let mut launched = RUNTIME
+            .lock()
+            .map_err(|_| FFIError::Runtime("the runtime lock is poisoned"))?;
+
+        if Arc::strong_count(&self.inner) > 1 {
+            return Err(FFIError::Runtime(
+                "the runtime is still used by other handles",
+            ));
+        }
+
+        let path = CString::new(model.as_ref()).map_err(|_| FFIError::ModelLoad)?;
+        unsafe {
+            if HFReloadInspireFace(path.as_ptr()).0 != SUCCESS {
+                return Err(FFIError::ModelLoad);
+            }
+        }
+
+        launched.model = Some(model.as_ref().to_string());
+
+        let loaded = LoadedModel::query(model.as_ref())?;
+        let mut current = self
+            .inner
+            .model
+            .lock()
+            .map_err(|_| FFIError::Runtime("the runtime lock is poisoned"))?;
+        *current = loaded;
+
+        Ok(())
+    }
+
+    /// Create a handler using the loaded model pack. See `InsightFace::with_session_pool` for the arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - `Option<usize>`
+    /// * `pool_size` - usize
+    pub fn handler<'a>(
+        &self,
+        chunk_size: Option<usize>,
+        pool_size: usize,
+    ) -> Result<InsightFace, FFIError<'a>> {
+        InsightFace::with_runtime(self, chunk_size, pool_size)
+    }
+
+    /// Read the model pack currently loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `read` - F
+    fn loaded<T, F: FnOnce(&LoadedModel) -> T>(&self, read: F) -> T {
+        // The model is only replaced as a whole, a poisoned lock still holds a valid model
+        let model = self
+            .inner
+            .model
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        read(&model)
+    }
+}
+
+impl Drop for RuntimeInner {
+    fn drop(&mut self) {
+        let mut current = RUNTIME.lock().unwrap_or_else(|err| err.into_inner());
+
+        // A new runtime may have taken over the SDK while this one was dropping
+        if current.runtime.strong_count() == 0 && std::ptr::eq(current.runtime.as_ptr(), self) {
+            unsafe {
+                HFTerminateInspireFace();
+            }
+            current.model = None;
+        }
+    }
+}
+
+/// Return whether the SDK has been launched
+fn is_launched<'a>() -> Result<bool, FFIError<'a>> {
+    let mut status = c_int(0);
+    unsafe {
+        if HFQueryInspireFaceLaunchStatus(&mut status).0 != SUCCESS {
+            return Err(FFIError::Runtime("unable to query the launch status"));
+        }
+    }
+
+    Ok(status.0 != 0)
+}
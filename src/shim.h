@@ -1,5 +1,6 @@
 #pragma once
 #include "inspireface.h"
+#include <cstring>
 
 // autocxx (built on cxx) cannot bind functions that take a `void**` argument,
 // i.e. a pointer to an opaque `void*` handle. In InspireFace, HFImageBitmap,
@@ -30,3 +31,122 @@ inline HFSession baklava_create_session_optional(HOption customOption, HFDetectM
                                                  trackByDetectModeFPS, &handle);
     return handle;
 }
+
+// HFMultipleFaceData is bound as an opaque type (its fields are raw arrays indexed by face), so the
+// per-face values are read through the accessors below rather than through the struct itself.
+
+inline HInt32 baklava_face_data_detected_num(HFMultipleFaceData *data) {
+    return data->detectedNum;
+}
+
+inline HFaceRect baklava_face_data_rect(HFMultipleFaceData *data, HInt32 index) {
+    return data->rects[index];
+}
+
+inline HInt32 baklava_face_data_track_id(HFMultipleFaceData *data, HInt32 index) {
+    return data->trackIds[index];
+}
+
+inline HFloat baklava_face_data_det_confidence(HFMultipleFaceData *data, HInt32 index) {
+    return data->detConfidence[index];
+}
+
+inline HFloat baklava_face_data_roll(HFMultipleFaceData *data, HInt32 index) {
+    return data->angles.roll[index];
+}
+
+inline HFloat baklava_face_data_yaw(HFMultipleFaceData *data, HInt32 index) {
+    return data->angles.yaw[index];
+}
+
+inline HFloat baklava_face_data_pitch(HFMultipleFaceData *data, HInt32 index) {
+    return data->angles.pitch[index];
+}
+
+// Copy an owned feature vector into a feature allocated with HFCreateFaceFeature. The size must match
+// the feature length of the loaded model.
+inline HResult baklava_face_feature_copy_from(HFFaceFeature *feature, const HFloat *data, HInt32 size) {
+    if (feature->data == nullptr || feature->size != size) {
+        return HERR_INVALID_FACE_FEATURE;
+    }
+
+    for (HInt32 i = 0; i < size; i++) {
+        feature->data[i] = data[i];
+    }
+
+    return HSUCCEED;
+}
+
+inline HFImageBitmap baklava_create_image_bitmap(HFImageBitmapData *data, HResult *result) {
+    HFImageBitmap handle = nullptr;
+    *result = HFCreateImageBitmap(data, &handle);
+    return handle;
+}
+
+// The face token is taken by pointer, as the opaque HFFaceBasicToken can't be passed by value from Rust.
+inline HFImageBitmap baklava_face_get_alignment_image(HFSession session, HFImageStream streamHandle, HFFaceBasicToken *singleFace,
+                                                      HResult *result) {
+    HFImageBitmap handle = nullptr;
+    *result = HFFaceGetFaceAlignmentImage(session, streamHandle, *singleFace, &handle);
+    return handle;
+}
+
+// Extract the feature of an aligned face image (112x112) straight into a caller owned buffer. The size of the
+// buffer must match the feature length of the loaded model.
+inline HResult baklava_face_feature_extract_with_alignment_image_cpy(HFSession session, HFImageStream streamHandle, HFloat *feature,
+                                                                     HInt32 size) {
+    HFFaceFeature target = {size, feature};
+    return HFFaceFeatureExtractWithAlignmentImage(session, streamHandle, target);
+}
+
+// FeatureHub takes HFFaceFeature & HFFaceFeatureIdentity by value which hold a pointer to the feature data. The
+// wrappers below build them around a caller owned buffer of the feature length.
+
+inline HResult baklava_feature_hub_insert_feature(HFaceId id, HFloat *feature, HInt32 size, HFaceId *allocId) {
+    HFFaceFeature target = {size, feature};
+    HFFaceFeatureIdentity identity = {id, &target};
+    return HFFeatureHubInsertFeature(identity, allocId);
+}
+
+inline HResult baklava_feature_hub_face_update(HFaceId id, HFloat *feature, HInt32 size) {
+    HFFaceFeature target = {size, feature};
+    HFFaceFeatureIdentity identity = {id, &target};
+    return HFFeatureHubFaceUpdate(identity);
+}
+
+// The id is set to -1 when no face is above the search threshold.
+inline HResult baklava_feature_hub_face_search(HFloat *feature, HInt32 size, HFloat *confidence, HFaceId *id) {
+    HFFaceFeature target = {size, feature};
+    HFFaceFeatureIdentity mostSimilar = {-1, nullptr};
+    HResult result = HFFeatureHubFaceSearch(target, confidence, &mostSimilar);
+    *id = mostSimilar.id;
+    return result;
+}
+
+// The arrays of the results are owned by FeatureHub and are only valid until the next search.
+inline HResult baklava_feature_hub_face_search_top_k(HFloat *feature, HInt32 size, HInt32 topK, HFSearchTopKResults *results) {
+    HFFaceFeature target = {size, feature};
+    return HFFeatureHubFaceSearchTopK(target, topK, results);
+}
+
+// Compare two caller owned feature buffers of the feature length without allocating HFFaceFeature.
+inline HResult baklava_face_comparison(HFloat *feature1, HFloat *feature2, HInt32 size, HFloat *result) {
+    HFFaceFeature first = {size, feature1};
+    HFFaceFeature second = {size, feature2};
+    return HFFaceComparison(first, second, result);
+}
+
+// autocxx can't bind the fixed size array of HFInspireFaceExtendedInformation, the information is copied into a
+// caller owned buffer which is always nul terminated.
+inline HResult baklava_query_extended_information(HChar *information, HInt32 size) {
+    HFInspireFaceExtendedInformation extended = {};
+    HResult result = HFQueryInspireFaceExtendedInformation(&extended);
+    if (size <= 0) {
+        return result;
+    }
+
+    HInt32 length = size < (HInt32)sizeof(extended.information) ? size : (HInt32)sizeof(extended.information);
+    strncpy(information, extended.information, length - 1);
+    information[length - 1] = '\0';
+    return result;
+}
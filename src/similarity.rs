@@ -0,0 +1,141 @@
+use crate::error::FFIError;
+use crate::ffi_wrapper::{
+    HFGetCosineSimilarityConverter, HFGetRecommendedCosineThreshold, HFSimilarityConverterConfig,
+    HFUpdateCosineSimilarityConverter,
+};
+use crate::SUCCESS;
+
+// Default configuration of the converter of the SDK
+const THRESHOLD: f32 = 0.48;
+const MIDDLE_SCORE: f32 = 0.6;
+const STEEPNESS: f32 = 8.;
+const OUTPUT_MIN: f32 = 0.01;
+const OUTPUT_MAX: f32 = 1.0;
+
+/// SimilarityConverter converts a cosine into a percentage of similarity with the sigmoid used by InspireFace.
+/// The cosine equal to `threshold` is converted to `middle_score`.
+///
+/// The configuration of the SDK is loaded from the model pack. Each handler reads it once created and can override
+/// it without affecting the other handlers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimilarityConverter {
+    /// Cosine above which two faces are considered to be the same person
+    pub threshold: f32,
+    /// Percentage the threshold is converted to
+    pub middle_score: f32,
+    /// Steepness of the curve
+    pub steepness: f32,
+    /// Minimum value of the percentage
+    pub output_min: f32,
+    /// Maximum value of the percentage
+    pub output_max: f32,
+}
+
+impl Default for SimilarityConverter {
+    fn default() -> Self {
+        Self {
+            threshold: THRESHOLD,
+            middle_score: MIDDLE_SCORE,
+            steepness: STEEPNESS,
+            output_min: OUTPUT_MIN,
+            output_max: OUTPUT_MAX,
+        }
+    }
+}
+
+impl SimilarityConverter {
+    /// Compute the percentage of similarity from a cosine. Based on the inspireface SDK formula
+    ///
+    /// # Arguments
+    ///
+    /// * `cosine` - f32
+    pub fn percentage(&self, cosine: f32) -> f64 {
+        let output_max = self.output_max as f64;
+        let output_min = self.output_min as f64;
+        let middle_score = self.middle_score as f64;
+
+        let bias = -f64::ln((output_max - middle_score) / (middle_score - output_min));
+        let output_scale = output_max - output_min;
+
+        let shifted_input = self.steepness as f64 * (cosine as f64 - self.threshold as f64);
+        let sigmoid = 1. / (1. + f64::exp(-shifted_input - bias));
+
+        sigmoid * output_scale + output_min
+    }
+
+    /// Read the configuration currently used by the SDK
+    pub(crate) fn query<'a>() -> Result<Self, FFIError<'a>> {
+        let mut config = HFSimilarityConverterConfig {
+            threshold: 0.,
+            middleScore: 0.,
+            steepness: 0.,
+            outputMin: 0.,
+            outputMax: 0.,
+        };
+
+        unsafe {
+            if HFGetCosineSimilarityConverter(&mut config).0 != SUCCESS {
+                return Err(FFIError::Model("unable to read the similarity converter"));
+            }
+        }
+
+        Ok(Self {
+            threshold: config.threshold,
+            middle_score: config.middleScore,
+            steepness: config.steepness,
+            output_min: config.outputMin,
+            output_max: config.outputMax,
+        })
+    }
+
+    /// Push the configuration to the SDK
+    pub(crate) fn update<'a>(&self) -> Result<(), FFIError<'a>> {
+        let config = HFSimilarityConverterConfig {
+            threshold: self.threshold,
+            middleScore: self.middle_score,
+            steepness: self.steepness,
+            outputMin: self.output_min,
+            outputMax: self.output_max,
+        };
+
+        unsafe {
+            if HFUpdateCosineSimilarityConverter(config).0 != SUCCESS {
+                return Err(FFIError::Model("unable to update the similarity converter"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the cosine threshold recommended by the loaded model pack
+pub(crate) fn recommended_threshold<'a>() -> Result<f32, FFIError<'a>> {
+    let mut threshold: f32 = 0.;
+    unsafe {
+        if HFGetRecommendedCosineThreshold(&mut threshold).0 != SUCCESS {
+            return Err(FFIError::Model("unable to read the recommended threshold"));
+        }
+    }
+
+    Ok(threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SimilarityConverter;
+
+    #[test]
+    fn expect_threshold_to_be_converted_to_the_middle_score() {
+        let converter = SimilarityConverter {
+            threshold: 0.42,
+            middle_score: 0.5,
+            ..Default::default()
+        };
+
+        assert!((converter.percentage(0.42) - 0.5).abs() < 1e-6);
+        assert!(converter.percentage(0.8) > 0.5);
+        assert!(converter.percentage(0.1) < 0.5);
+        assert!(converter.percentage(-1.) >= converter.output_min as f64);
+        assert!(converter.percentage(1.) <= converter.output_max as f64);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::index::dot;
+use crate::similarity::SimilarityConverter;
+use crate::tracking::BestFrame;
+use crate::InsightFace;
+
+/// FaceTemplate is the fusion of several embeddings of the same person into a single one. It's the weighted mean of
+/// the L2-normalized embeddings, normalized again. Comparing a probe against a template only needs one comparison
+/// and is less sensitive to the noise of a single enrollment image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceTemplate {
+    embedding: FaceEmbedding,
+    samples: usize,
+}
+
+impl FaceTemplate {
+    /// Create a template where every embedding has the same weight
+    ///
+    /// # Arguments
+    ///
+    /// * `embeddings` - `&[FaceEmbedding]`
+    pub fn new<'a>(embeddings: &[FaceEmbedding]) -> Result<Self, FFIError<'a>> {
+        Self::with_weights(embeddings, &vec![1.; embeddings.len()])
+    }
+
+    /// Create a template where each embedding is weighted (e.g. by the quality of the face it was extracted from)
+    ///
+    /// # Arguments
+    ///
+    /// * `embeddings` - `&[FaceEmbedding]`
+    /// * `weights` - `&[f32]`
+    pub fn with_weights<'a>(
+        embeddings: &[FaceEmbedding],
+        weights: &[f32],
+    ) -> Result<Self, FFIError<'a>> {
+        if embeddings.len() != weights.len() {
+            return Err(FFIError::Template(
+                "each embedding needs to have exactly one weight",
+            ));
+        }
+
+        if weights
+            .iter()
+            .any(|weight| !weight.is_finite() || *weight < 0.)
+            || weights.iter().sum::<f32>() <= 0.
+        {
+            return Err(FFIError::Template(
+                "weights need to be positive and not all zero",
+            ));
+        }
+
+        let dimension = embeddings
+            .first()
+            .map(FaceEmbedding::len)
+            .ok_or(FFIError::Template("at least one embedding is required"))?;
+
+        // The template is tagged with the model pack of the tagged embeddings, if any
+        let reference = embeddings
+            .iter()
+            .find(|embedding| embedding.model().is_some())
+            .unwrap_or(&embeddings[0]);
+
+        let mut data = vec![0.; dimension];
+        for (embedding, weight) in embeddings.iter().zip(weights) {
+            if embedding.len() != dimension {
+                return Err(FFIError::Template(
+                    "the embeddings do not have the same dimension",
+                ));
+            }
+
+            if !embedding.is_compatible(reference) {
+                return Err(FFIError::Template(
+                    "the embeddings have been extracted by different model packs",
+                ));
+            }
+
+            let norm = norm(embedding.as_slice())
+                .ok_or(FFIError::Template("an embedding can not be normalized"))?;
+
+            for (acc, value) in data.iter_mut().zip(embedding.as_slice()) {
+                *acc += weight * value / norm;
+            }
+        }
+
+        // Embeddings pointing in opposite directions may cancel each other out
+        let norm = norm(&data).ok_or(FFIError::Template("the embeddings cancel each other out"))?;
+
+        Ok(Self {
+            embedding: reference.with_same_model(data.into_iter().map(|v| v / norm).collect()),
+            samples: embeddings.len(),
+        })
+    }
+
+    /// Create a template out of the best frames of a sequence, weighted by their score
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - `&[BestFrame]`
+    pub fn from_best_frames<'a>(frames: &[BestFrame]) -> Result<Self, FFIError<'a>> {
+        let (embeddings, weights): (Vec<_>, Vec<_>) = frames
+            .iter()
+            .map(|frame| (frame.embedding.clone(), frame.score.score))
+            .unzip();
+
+        Self::with_weights(&embeddings, &weights)
+    }
+
+    /// Return the fused embedding which is L2-normalized
+    pub fn embedding(&self) -> &FaceEmbedding {
+        &self.embedding
+    }
+
+    /// Return the number of embeddings the template has been built from
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Consume the template and return the fused embedding
+    pub fn into_embedding(self) -> FaceEmbedding {
+        self.embedding
+    }
+
+    /// Compare a probe against the template. Return the cosine and the percentage of similarity computed with the
+    /// converter, usually the one of the handler which extracted the embeddings.
+    ///
+    /// # Arguments
+    ///
+    /// * `probe` - &FaceEmbedding
+    /// * `converter` - &SimilarityConverter
+    pub fn compare<'a>(
+        &self,
+        probe: &FaceEmbedding,
+        converter: &SimilarityConverter,
+    ) -> Result<(f32, f64), FFIError<'a>> {
+        if probe.len() != self.embedding.len() {
+            return Err(FFIError::Template(
+                "the probe does not have the dimension of the template",
+            ));
+        }
+
+        if !probe.is_compatible(&self.embedding) {
+            return Err(FFIError::Template(
+                "the probe has been extracted by another model pack",
+            ));
+        }
+
+        let norm =
+            norm(probe.as_slice()).ok_or(FFIError::Template("the probe can not be normalized"))?;
+        let cosine = dot(self.embedding.as_slice(), probe.as_slice()) / norm;
+
+        Ok((cosine, converter.percentage(cosine)))
+    }
+}
+
+impl InsightFace {
+    /// Build a template out of the faces of several images of the same person
+    ///
+    /// # Arguments
+    ///
+    /// * `img_paths` - `&[S]`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use baklava::{InsightFace, Methodology};
+    ///
+    /// let mut handler = InsightFace::new("./Megatron", None).unwrap();
+    /// let template = handler.build_template(&["./face1_test.png", "./face2_test.png"]).unwrap();
+    ///
+    /// let (cosine, percentage) = handler
+    ///     .prepare_template(&template)
+    ///     .unwrap()
+    ///     .prepare_target_image("./face1_test.png")
+    ///     .unwrap()
+    ///     .compare_images(Methodology::Mean)
+    ///     .unwrap();
+    /// ```
+    pub fn build_template<'a, S: AsRef<str>>(
+        &mut self,
+        img_paths: &[S],
+    ) -> Result<FaceTemplate, FFIError<'a>> {
+        let embeddings = img_paths
+            .iter()
+            .map(|img_path| self.extract_embedding(img_path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        FaceTemplate::new(&embeddings)
+    }
+
+    /// Use a template as the only source image. The target is then compared with a single comparison.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - &FaceTemplate
+    pub fn prepare_template<'a>(
+        &mut self,
+        template: &FaceTemplate,
+    ) -> Result<&mut Self, FFIError<'a>> {
+        self.prepare_embeddings(std::slice::from_ref(template.embedding()))
+    }
+}
+
+/// Return the L2 norm of the vector when it can be used to normalize it
+///
+/// # Arguments
+///
+/// * `data` - &[f32]
+fn norm(data: &[f32]) -> Option<f32> {
+    let norm = dot(data, data).sqrt();
+
+    (norm > 0. && norm.is_finite()).then_some(norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaceTemplate;
+    use crate::embedding::FaceEmbedding;
+    use crate::model::{ModelInfo, SdkVersion};
+    use crate::similarity::SimilarityConverter;
+    use std::sync::Arc;
+
+    fn model(digest: u64) -> Arc<ModelInfo> {
+        Arc::new(ModelInfo {
+            version: SdkVersion {
+                major: 1,
+                minor: 2,
+                patch: 0,
+            },
+            pack: "Megatron".to_string(),
+            digest,
+            information: String::new(),
+            feature_length: 2,
+        })
+    }
+
+    #[test]
+    fn expect_template_to_be_the_mean_of_normalized_embeddings() {
+        // The scale of an embedding does not change its weight
+        let embeddings = [
+            FaceEmbedding::new(vec![10., 0.]),
+            FaceEmbedding::new(vec![0., 1.]),
+        ];
+
+        let template = FaceTemplate::new(&embeddings).unwrap();
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+
+        assert_eq!(template.samples(), 2);
+        assert!(template
+            .embedding()
+            .as_slice()
+            .iter()
+            .all(|v| (v - expected).abs() < 1e-6));
+
+        let (cosine, _) = template
+            .compare(
+                &FaceEmbedding::new(vec![3., 3.]),
+                &SimilarityConverter::default(),
+            )
+            .unwrap();
+        assert!((cosine - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn expect_weights_to_be_applied() {
+        let embeddings = [
+            FaceEmbedding::new(vec![1., 0.]),
+            FaceEmbedding::new(vec![0., 1.]),
+        ];
+
+        let template = FaceTemplate::with_weights(&embeddings, &[3., 1.]).unwrap();
+        let data = template.embedding().as_slice();
+
+        assert!(data[0] > data[1]);
+        assert!(FaceTemplate::with_weights(&embeddings, &[1.]).is_err());
+        assert!(FaceTemplate::with_weights(&embeddings, &[0., 0.]).is_err());
+        assert!(FaceTemplate::new(&[]).is_err());
+    }
+
+    #[test]
+    fn expect_embeddings_of_different_model_packs_to_be_rejected() {
+        let megatron = model(1);
+        let embeddings = [
+            FaceEmbedding::new(vec![1., 0.]),
+            FaceEmbedding::with_model(vec![0., 1.], megatron.clone()),
+        ];
+
+        let template = FaceTemplate::new(&embeddings).unwrap();
+        assert_eq!(template.embedding().model(), Some(megatron.as_ref()));
+
+        let probe = FaceEmbedding::with_model(vec![1., 1.], model(2));
+        assert!(template
+            .compare(&probe, &SimilarityConverter::default())
+            .is_err());
+        assert!(FaceTemplate::new(&[embeddings[1].clone(), probe]).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::embedding::FaceEmbedding;
+use crate::error::FFIError;
+use crate::face::{self, DetectedFace, FacePose};
+use crate::ffi_wrapper::{
+    HFDetectMode, HFFaceFeatureExtractCpy, HFFaceQualityDetect, HFSessionClearTrackingFace,
+    HF_ENABLE_FACE_POSE, HF_ENABLE_FACE_RECOGNITION, HF_ENABLE_QUALITY,
+};
+use crate::handle::Session;
+use crate::image::FaceImage;
+use crate::model::ModelInfo;
+use crate::runtime::Runtime;
+use crate::{InsightFace, SUCCESS};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{cmp::Reverse, fs, path::Path};
+
+// Extensions of the frames which are picked up when reading a directory of extracted frames
+const FRAME_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// TrackingSession is an InspireFace session running in the light track mode. Faces keep the same
+/// track id across the frames given to the session, which allows to follow a person over a video clip.
+pub struct TrackingSession {
+    session: Session,
+    model: Arc<ModelInfo>,
+    // Declared last so that the SDK outlives the session
+    _runtime: Runtime,
+}
+
+/// FrameScore details how a frame has been scored by the best frame selection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameScore {
+    /// Face quality computed by InspireFace ranging from 0 to 1
+    pub quality: f32,
+    /// Detection confidence of the face ranging from 0 to 1
+    pub confidence: f32,
+    pub pose: FacePose,
+    /// Final score used to rank the frames
+    pub score: f32,
+}
+
+/// BestFrame is a frame kept by the best frame selection along with the embedding of the dominant face
+#[derive(Debug, Clone)]
+pub struct BestFrame {
+    /// Position of the frame in the given sequence
+    pub index: usize,
+    pub path: String,
+    pub face: DetectedFace,
+    pub score: FrameScore,
+    pub embedding: FaceEmbedding,
+}
+
+impl TrackingSession {
+    /// Create a new tracking session. The InsightFace handler is required as the model needs to be launched beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - &InsightFace
+    /// * `max_faces` - i32
+    pub fn new<'a>(handler: &InsightFace, max_faces: i32) -> Result<Self, FFIError<'a>> {
+        let session = Session::new(
+            HF_ENABLE_FACE_RECOGNITION | HF_ENABLE_QUALITY | HF_ENABLE_FACE_POSE,
+            HFDetectMode::HF_DETECT_MODE_LIGHT_TRACK,
+            max_faces,
+        )?;
+
+        Ok(Self {
+            session,
+            model: handler.model_info(),
+            _runtime: handler.runtime().clone(),
+        })
+    }
+
+    /// Track the faces of the next frame of the sequence
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - S
+    pub fn track<'a, S: AsRef<str>>(
+        &mut self,
+        frame: S,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        face::execute_face_track(self.session.as_ptr(), frame.as_ref(), |_, _, data| {
+            face::read_faces(data)
+        })
+    }
+
+    /// Track the faces of the next frame of the sequence from an in-memory image (e.g. a decoded video frame)
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - &FaceImage
+    pub fn track_image<'a>(
+        &mut self,
+        frame: &FaceImage,
+    ) -> Result<Vec<DetectedFace>, FFIError<'a>> {
+        face::execute_face_track_on_bitmap(
+            self.session.as_ptr(),
+            &frame.to_bitmap()?,
+            |_, _, data| face::read_faces(data),
+        )
+    }
+
+    /// Clear the faces tracked so far. This needs to be called before tracking a new sequence of frames.
+    pub fn reset<'a>(&mut self) -> Result<(), FFIError<'a>> {
+        unsafe {
+            if HFSessionClearTrackingFace(self.session.as_ptr()).0 != SUCCESS {
+                return Err(FFIError::FaceTrack("Unable to clear the tracked faces"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Track the dominant face over a sequence of frames and return the `top_k` frames with the best score.
+    /// The dominant face is the face which is the largest on most of the frames. Each frame is scored by
+    /// multiplying the quality, the detection confidence and how frontal the pose of the face is.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - `&[S]`
+    /// * `top_k` - usize
+    pub fn select_best_frames<'a, S: AsRef<str>>(
+        &mut self,
+        frames: &[S],
+        top_k: usize,
+    ) -> Result<Vec<BestFrame>, FFIError<'a>> {
+        self.reset()?;
+
+        let mut candidates = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            if let Some((face, score, embedding)) = self.score_frame(frame.as_ref())? {
+                candidates.push(BestFrame {
+                    index,
+                    path: frame.as_ref().to_string(),
+                    face,
+                    score,
+                    embedding,
+                });
+            }
+        }
+
+        Ok(rank_frames(candidates, top_k))
+    }
+
+    /// Same as `select_best_frames` but over a directory of extracted frames. The frames are sorted by their name.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - P
+    /// * `top_k` - usize
+    pub fn select_best_frames_from_dir<'a, P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        top_k: usize,
+    ) -> Result<Vec<BestFrame>, FFIError<'a>> {
+        let mut frames = fs::read_dir(dir)
+            .map_err(|_| FFIError::IO("Unable to read the frames directory"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| FRAME_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                    .unwrap_or(false)
+            })
+            .filter_map(|path| path.to_str().map(String::from))
+            .collect::<Vec<_>>();
+
+        frames.sort();
+
+        self.select_best_frames(&frames, top_k)
+    }
+
+    /// Track a frame and score the largest face of it. Returns None when no face has been found.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - &str
+    fn score_frame<'a>(
+        &mut self,
+        frame: &str,
+    ) -> Result<Option<(DetectedFace, FrameScore, FaceEmbedding)>, FFIError<'a>> {
+        let model = self.model.clone();
+
+        face::execute_face_track(
+            self.session.as_ptr(),
+            frame,
+            |session, stream_ptr, data| unsafe {
+                let faces = face::read_faces(data)?;
+                let Some((idx, face)) = faces
+                    .into_iter()
+                    .enumerate()
+                    .max_by_key(|(_, face)| face.rect.area())
+                else {
+                    return Ok(None);
+                };
+
+                let token = face::read_tokens(data)?
+                    .get(idx)
+                    .ok_or(FFIError::FaceTrack("Unable to get the token of the face"))?;
+
+                let mut quality: f32 = 0.0;
+                if HFFaceQualityDetect(session, token, &mut quality).0 != SUCCESS {
+                    return Err(FFIError::FaceTrack(
+                        "Unable to compute the quality of the face",
+                    ));
+                }
+
+                let mut data = vec![0.; model.feature_length];
+                if HFFaceFeatureExtractCpy(session, stream_ptr, token, data.as_mut_ptr()).0
+                    != SUCCESS
+                {
+                    return Err(FFIError::FaceTrack(
+                        "Unable to extract feature from stream_ptr",
+                    ));
+                }
+
+                let score = FrameScore::new(quality, face.confidence, face.pose);
+
+                Ok(Some((
+                    face,
+                    score,
+                    FaceEmbedding::with_model(data, model.clone()),
+                )))
+            },
+        )
+    }
+}
+
+impl FrameScore {
+    /// Create a new frame score
+    ///
+    /// # Arguments
+    ///
+    /// * `quality` - f32
+    /// * `confidence` - f32
+    /// * `pose` - FacePose
+    pub fn new(quality: f32, confidence: f32, pose: FacePose) -> Self {
+        Self {
+            quality,
+            confidence,
+            pose,
+            score: quality.max(0.) * confidence.max(0.) * pose.frontal_score(),
+        }
+    }
+}
+
+/// Keep the frames of the dominant face and return the `top_k` ones with the highest score.
+/// When several faces are dominant on the same number of frames, the one tracked first is kept.
+///
+/// # Arguments
+///
+/// * `candidates` - `Vec<BestFrame>`
+/// * `top_k` - usize
+fn rank_frames(candidates: Vec<BestFrame>, top_k: usize) -> Vec<BestFrame> {
+    let mut occurrences: HashMap<i32, usize> = HashMap::new();
+    for candidate in candidates.iter() {
+        *occurrences.entry(candidate.face.track_id).or_default() += 1;
+    }
+
+    let Some(dominant) = occurrences
+        .into_iter()
+        .max_by_key(|(track_id, count)| (*count, Reverse(*track_id)))
+        .map(|(track_id, _)| track_id)
+    else {
+        return vec![];
+    };
+
+    let mut frames = candidates
+        .into_iter()
+        .filter(|candidate| candidate.face.track_id == dominant)
+        .collect::<Vec<_>>();
+
+    frames.sort_by(|a, b| b.score.score.total_cmp(&a.score.score));
+    frames.truncate(top_k);
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank_frames, BestFrame, FrameScore};
+    use crate::embedding::FaceEmbedding;
+    use crate::face::{DetectedFace, FacePose, FaceRect};
+
+    fn candidate(index: usize, track_id: i32, quality: f32) -> BestFrame {
+        BestFrame {
+            index,
+            path: format!("frame_{index}.png"),
+            face: DetectedFace {
+                track_id,
+                rect: FaceRect::default(),
+                confidence: 1.,
+                pose: FacePose::default(),
+                landmarks: Default::default(),
+            },
+            score: FrameScore::new(quality, 1., FacePose::default()),
+            embedding: FaceEmbedding::new(vec![]),
+        }
+    }
+
+    #[test]
+    fn expect_to_keep_best_frames_of_dominant_face() {
+        let frames = rank_frames(
+            vec![
+                candidate(0, 1, 0.5),
+                candidate(1, 2, 0.99),
+                candidate(2, 1, 0.9),
+                candidate(3, 1, 0.7),
+            ],
+            2,
+        );
+
+        let indexes = frames.iter().map(|frame| frame.index).collect::<Vec<_>>();
+        assert_eq!(indexes, vec![2, 3]);
+    }
+
+    #[test]
+    fn expect_frontal_pose_to_score_higher() {
+        let frontal = FrameScore::new(0.8, 0.9, FacePose::default());
+        let profile = FrameScore::new(
+            0.8,
+            0.9,
+            FacePose {
+                roll: 0.,
+                yaw: 60.,
+                pitch: 10.,
+            },
+        );
+
+        assert!(frontal.score > profile.score);
+    }
+}